@@ -0,0 +1,42 @@
+#![no_main]
+sp1_zkvm::entrypoint!(main);
+
+use rsp_client_executor::public_values::{AggregationPublicValues, PublicValues};
+use sha2::{Digest, Sha256};
+
+/// The comma-separated hashes of the verification keys of the client programs whose proofs can be
+/// aggregated, written by the build script of the host when it builds this program.
+const CLIENT_VKEY_HASHES: &str = include_str!(env!("RSP_CLIENT_VKEY_HASHES_PATH"));
+
+pub fn main() {
+    // Read the verification key of the client program and the public values of the blocks.
+    let vkey = sp1_zkvm::io::read::<[u32; 8]>();
+    let block_public_values = sp1_zkvm::io::read::<Vec<Vec<u8>>>();
+
+    // Only aggregate the proofs of the client programs built along with this program.
+    let vkey_hash = AggregationPublicValues::vkey_hash(&vkey);
+    assert!(
+        CLIENT_VKEY_HASHES.split(',').any(|hash| hash == vkey_hash.to_string()),
+        "unknown client program verification key {vkey_hash}"
+    );
+
+    // Verify the proofs of the blocks, whose public values start with the ones common to all the
+    // client programs.
+    let blocks = block_public_values
+        .iter()
+        .map(|public_values| {
+            let digest = Sha256::digest(public_values);
+            sp1_zkvm::lib::verify::verify_sp1_proof(&vkey, &digest.into());
+
+            PublicValues::abi_decode(&public_values[..PublicValues::ABI_ENCODED_SIZE])
+                .expect("invalid public values")
+        })
+        .collect::<Vec<_>>();
+
+    // Check that the blocks are consecutive.
+    let public_values =
+        AggregationPublicValues::new(vkey_hash, &blocks).expect("failed to aggregate the blocks");
+
+    // Commit the public values.
+    sp1_zkvm::io::commit_slice(&public_values.abi_encode());
+}
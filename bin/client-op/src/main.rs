@@ -1,19 +1,38 @@
 #![no_main]
 sp1_zkvm::entrypoint!(main);
 
-use rsp_client_executor::{executor::OpClientExecutor, io::OpClientExecutorInput};
+use rsp_client_executor::{
+    executor::OpClientExecutor,
+    io::{ClientState, NodeStore, OpClientExecutorInput},
+    optimism,
+    public_values::{OpPublicValues, PublicValues},
+};
 use std::sync::Arc;
 
 pub fn main() {
     // Read the input.
     let input = sp1_zkvm::io::read_vec();
-    let input = bincode::deserialize::<OpClientExecutorInput>(&input).unwrap();
+    let input_hash = PublicValues::hash_input(&input);
+    let input = bincode::deserialize::<OpClientExecutorInput<NodeStore>>(&input).unwrap();
+    let input = input.map_state(|state| ClientState::try_from(state).unwrap());
+
+    // Decode the L1 origin, which the execution of the block binds to its hash.
+    let l1_origin = optimism::l1_origin(&input.current_block.body.transactions)
+        .expect("missing L1 attributes deposit transaction");
 
     // Execute the block.
     let executor = OpClientExecutor::optimism(Arc::new((&input.genesis).try_into().unwrap()));
-    let header = executor.execute(input).expect("failed to execute client");
-    let block_hash = header.hash_slow();
+    let (public_values, output_root) =
+        executor.execute_with_output_root(input, input_hash).expect("failed to execute client");
 
-    // Commit the block hash.
-    sp1_zkvm::io::commit(&block_hash);
+    // Commit the public values, followed by the output root and the L1 origin.
+    sp1_zkvm::io::commit_slice(&public_values.abi_encode());
+    sp1_zkvm::io::commit_slice(
+        &OpPublicValues {
+            output_root: output_root.hash(),
+            l1_origin_hash: l1_origin.hash,
+            l1_origin_number: l1_origin.number,
+        }
+        .abi_encode(),
+    );
 }
@@ -0,0 +1,26 @@
+#![no_main]
+sp1_zkvm::entrypoint!(main);
+
+use rsp_client_executor::{
+    executor::EthClientExecutor,
+    io::{ClientState, EthClientExecutorRangeInput, NodeStore},
+    public_values::PublicValues,
+};
+use std::sync::Arc;
+
+pub fn main() {
+    // Read the input.
+    let input = sp1_zkvm::io::read_vec();
+    let input_hash = PublicValues::hash_input(&input);
+    let input = bincode::deserialize::<EthClientExecutorRangeInput<NodeStore>>(&input).unwrap();
+    let input = input.map_state(|state| ClientState::try_from(state).unwrap());
+
+    // Execute the blocks.
+    let executor = EthClientExecutor::eth(Arc::new((&input.genesis).try_into().unwrap()));
+    let public_values = executor
+        .execute_range_with_public_values(input, input_hash)
+        .expect("failed to execute client");
+
+    // Commit the public values.
+    sp1_zkvm::io::commit_slice(&public_values.abi_encode());
+}
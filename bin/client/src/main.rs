@@ -1,22 +1,25 @@
 #![no_main]
 sp1_zkvm::entrypoint!(main);
 
-use rsp_client_executor::{executor::EthClientExecutor, io::EthClientExecutorInput};
+use rsp_client_executor::{
+    executor::EthClientExecutor,
+    io::{ClientState, EthClientExecutorInput, NodeStore},
+    public_values::PublicValues,
+};
 use std::sync::Arc;
 
 pub fn main() {
     // Read the input.
     let input = sp1_zkvm::io::read_vec();
-    let input = bincode::deserialize::<EthClientExecutorInput>(&input).unwrap();
+    let input_hash = PublicValues::hash_input(&input);
+    let input = bincode::deserialize::<EthClientExecutorInput<NodeStore>>(&input).unwrap();
+    let input = input.map_state(|state| ClientState::try_from(state).unwrap());
 
     // Execute the block.
-    let executor = EthClientExecutor::eth(
-        Arc::new((&input.genesis).try_into().unwrap()),
-        input.custom_beneficiary,
-    );
-    let header = executor.execute(input).expect("failed to execute client");
-    let block_hash = header.hash_slow();
+    let executor = EthClientExecutor::eth(Arc::new((&input.genesis).try_into().unwrap()));
+    let public_values =
+        executor.execute_with_public_values(input, input_hash).expect("failed to execute client");
 
-    // Commit the block hash.
-    sp1_zkvm::io::commit(&block_hash);
+    // Commit the public values.
+    sp1_zkvm::io::commit_slice(&public_values.abi_encode());
 }
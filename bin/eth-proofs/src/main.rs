@@ -8,7 +8,7 @@ use cli::Args;
 use eth_proofs::EthProofsClient;
 use futures::{future::ready, StreamExt};
 use pager_duty::send_alert;
-use rsp_host_executor::{create_eth_block_execution_strategy_factory, BlockExecutor, FullExecutor};
+use rsp_host_executor::{create_eth_host_executor, BlockExecutor, FullExecutor};
 use sp1_sdk::include_elf;
 use tokio::time::sleep;
 use tracing::{error, info};
@@ -37,8 +37,7 @@ async fn main() -> eyre::Result<()> {
     let config = args.as_config().await?;
 
     let elf = include_elf!("rsp-client").to_vec();
-    let block_execution_strategy_factory =
-        create_eth_block_execution_strategy_factory(&config.genesis, None);
+    let host_executor = create_eth_host_executor(&config.genesis);
 
     let eth_proofs_client = EthProofsClient::new(
         args.eth_proofs_cluster_id,
@@ -58,13 +57,8 @@ async fn main() -> eyre::Result<()> {
     let mut stream =
         subscription.into_stream().filter(|h| ready(h.number % args.block_interval == 0));
 
-    let mut executor = FullExecutor::new(
-        http_provider.clone(),
-        elf,
-        block_execution_strategy_factory,
-        eth_proofs_client,
-        config,
-    );
+    let mut executor =
+        FullExecutor::new(http_provider.clone(), elf, host_executor, eth_proofs_client, config);
 
     info!("Latest block number: {}", http_provider.get_block_number().await?);
 
@@ -1,6 +1,74 @@
-use sp1_helper::build_program;
+use sp1_helper::{build_program_with_args, BuildArgs};
+use sp1_sdk::{HashableKey, ProverClient};
+use std::path::Path;
 
 fn main() {
-    build_program("../client");
-    build_program("../client-op");
+    // Build the clients with the arena-based trie, to compare their cycle counts, and without the
+    // precompile annotations if the host is built without them.
+    let mut features = vec![];
+    if std::env::var_os("CARGO_FEATURE_ARENA_TRIE").is_some() {
+        features.push("arena-trie".to_string());
+    }
+    let annotate_precompiles = std::env::var_os("CARGO_FEATURE_PRECOMPILE_ANNOTATIONS").is_some();
+    if annotate_precompiles {
+        features.push("precompile-annotations".to_string());
+    }
+
+    // The ELFs of the clients are also copied to the output directory, to compute their
+    // verification keys.
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    for (path, name) in [
+        ("../client", "rsp-client"),
+        ("../client-op", "rsp-client-op"),
+        ("../client-range", "rsp-client-range"),
+    ] {
+        build_program_with_args(
+            path,
+            BuildArgs {
+                features: features.clone(),
+                no_default_features: !annotate_precompiles,
+                elf_name: Some(name.to_string()),
+                output_directory: Some(out_dir.clone()),
+                ..Default::default()
+            },
+        );
+    }
+
+    // Pin the verification keys of the clients whose proofs are aggregated in the aggregation
+    // program, which would otherwise trust the key supplied by the host. The proofs of
+    // `rsp-client-range` commit different public values, and are not aggregated.
+    //
+    // The programs are not built when the build is skipped, e.g. with `SP1_SKIP_PROGRAM_BUILD`,
+    // in which case the aggregation program is not built either, rather than built to reject
+    // every proof.
+    let Some(vkey_hashes) = ["rsp-client", "rsp-client-op"]
+        .iter()
+        .map(|name| vkey_hash(&Path::new(&out_dir).join(name)))
+        .collect::<Option<Vec<_>>>()
+    else {
+        println!(
+            "cargo:warning=Skipping the aggregation program, the client programs were not built"
+        );
+        return;
+    };
+
+    // The aggregation program includes the file, so that it is rebuilt when the hashes change.
+    // The file is only written when they do, not to rebuild it needlessly.
+    let vkey_hashes_path = Path::new(&out_dir).join("client-vkey-hashes.txt");
+    let vkey_hashes = vkey_hashes.join(",");
+    if std::fs::read_to_string(&vkey_hashes_path).ok().as_deref() != Some(vkey_hashes.as_str()) {
+        std::fs::write(&vkey_hashes_path, vkey_hashes).unwrap();
+    }
+    std::env::set_var("RSP_CLIENT_VKEY_HASHES_PATH", &vkey_hashes_path);
+    build_program_with_args("../aggregation", BuildArgs::default());
+}
+
+/// Returns the hash of the verification key of the program at `elf_path`, formatted like
+/// `AggregationPublicValues::vkey_hash`, or `None` if the program was not built.
+fn vkey_hash(elf_path: &Path) -> Option<String> {
+    let elf = std::fs::read(elf_path).ok()?;
+    let (_, vk) = ProverClient::builder().cpu().build().setup(&elf);
+    let hash = vk.hash_u32().iter().map(|word| format!("{word:08x}")).collect::<String>();
+
+    Some(format!("0x{hash}"))
 }
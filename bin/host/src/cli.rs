@@ -15,6 +15,16 @@ pub struct HostArgs {
     #[clap(long)]
     pub block_number: u64,
 
+    /// The block number of the last block to execute along with `--block-number` and the blocks
+    /// between them, in a single run of the client program. Only supported on Ethereum chains.
+    #[clap(long)]
+    pub last_block_number: Option<u64>,
+
+    /// Whether to prove the blocks from `--block-number` to `--last-block-number` one by one and
+    /// aggregate their proofs into a single one, instead of executing them in a single run.
+    #[clap(long, requires = "last_block_number")]
+    pub aggregate: bool,
+
     #[clap(flatten)]
     pub provider: ProviderArgs,
 
@@ -22,7 +32,8 @@ pub struct HostArgs {
     #[clap(long)]
     pub genesis_path: Option<PathBuf>,
 
-    /// The custom beneficiary address, used with Clique consensus.
+    /// The expected block beneficiary, checked against the signer of the block with Clique
+    /// consensus.
     #[clap(long)]
     pub custom_beneficiary: Option<Address>,
 
@@ -1,29 +1,15 @@
 use alloy_consensus::BlockHeader;
-use csv::WriterBuilder;
+use csv::{ReaderBuilder, WriterBuilder};
 use reth_primitives::NodePrimitives;
 use reth_primitives_traits::BlockBody;
 use rsp_client_executor::io::ClientExecutorInput;
 use rsp_host_executor::ExecutionHooks;
-use serde::{Deserialize, Serialize};
 use sp1_core_executor::syscalls::SyscallCode;
 use sp1_sdk::ExecutionReport;
-use std::{fs::OpenOptions, path::PathBuf};
-
-#[derive(Serialize, Deserialize)]
-struct ExecutionReportData {
-    chain_id: u64,
-    block_number: u64,
-    gas_used: u64,
-    tx_count: usize,
-    number_cycles: u64,
-    number_syscalls: u64,
-    bn_add_cycles: u64,
-    bn_mul_cycles: u64,
-    bn_pair_cycles: u64,
-    kzg_point_eval_cycles: u64,
-    keccak_count: u64,
-    secp256k1_decompress_count: u64,
-}
+use std::{
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug)]
 pub struct PersistExecutionReport {
@@ -52,41 +38,99 @@ impl ExecutionHooks for PersistExecutionReport {
         let number_cycles = execution_report.total_instruction_count();
         let number_syscalls = execution_report.total_syscall_count();
 
-        let bn_add_cycles = *execution_report.cycle_tracker.get("precompile-bn-add").unwrap_or(&0);
-        let bn_mul_cycles = *execution_report.cycle_tracker.get("precompile-bn-mul").unwrap_or(&0);
-        let bn_pair_cycles =
-            *execution_report.cycle_tracker.get("precompile-bn-pair").unwrap_or(&0);
-        let kzg_point_eval_cycles =
-            *execution_report.cycle_tracker.get("precompile-kzg-point-evaluation").unwrap_or(&0);
+        let state_root_cycles =
+            *execution_report.cycle_tracker.get("compute-state-root").unwrap_or(&0);
         let keccak_count = execution_report.syscall_counts[SyscallCode::KECCAK_PERMUTE];
         let secp256k1_decompress_count =
             execution_report.syscall_counts[SyscallCode::SECP256K1_DECOMPRESS];
 
-        let report_data = ExecutionReportData {
-            chain_id: self.chain_id,
-            block_number,
-            gas_used,
-            tx_count,
-            number_cycles,
-            number_syscalls,
-            bn_add_cycles,
-            bn_mul_cycles,
-            bn_pair_cycles,
-            kzg_point_eval_cycles,
-            keccak_count,
-            secp256k1_decompress_count,
-        };
-
-        // Open the file for appending or create it if it doesn't exist
-        let file = OpenOptions::new().append(true).create(true).open(self.report_path.clone())?;
-
-        // Check if the file is empty
-        let file_is_empty = file.metadata()?.len() == 0;
-
-        let mut writer = WriterBuilder::new().has_headers(file_is_empty).from_writer(file);
-        writer.serialize(report_data)?;
+        let mut columns = vec![
+            ("chain_id".to_string(), self.chain_id),
+            ("block_number".to_string(), block_number),
+            ("gas_used".to_string(), gas_used),
+            ("tx_count".to_string(), tx_count as u64),
+            ("number_cycles".to_string(), number_cycles),
+            ("number_syscalls".to_string(), number_syscalls),
+            ("state_root_cycles".to_string(), state_root_cycles),
+            ("keccak_count".to_string(), keccak_count),
+            ("secp256k1_decompress_count".to_string(), secp256k1_decompress_count),
+        ];
+
+        // One column per annotated precompile called in the block, including the precompiles of
+        // custom chains.
+        let mut precompiles: Vec<_> = execution_report
+            .cycle_tracker
+            .iter()
+            .filter_map(|(key, cycles)| {
+                Some((column_name(key.strip_prefix("precompile-")?), *cycles))
+            })
+            .collect();
+        precompiles.sort();
+        columns.extend(precompiles);
+
+        // Add the columns not reported yet after the existing ones, which rewrites the report to
+        // pad the previous rows with zeros. Otherwise, the row is appended.
+        let mut header = read_header(&self.report_path)?;
+        let new_columns: Vec<_> = columns
+            .iter()
+            .map(|(name, _)| name)
+            .filter(|name| !header.contains(name))
+            .cloned()
+            .collect();
+        if !new_columns.is_empty() {
+            let records = read_records(&self.report_path)?;
+            header.extend(new_columns);
+
+            let mut writer = WriterBuilder::new().from_path(&self.report_path)?;
+            writer.write_record(&header)?;
+            for mut record in records {
+                record.resize(header.len(), "0".to_string());
+                writer.write_record(&record)?;
+            }
+            writer.flush()?;
+        }
+
+        let record = header.iter().map(|name| {
+            columns
+                .iter()
+                .find(|(column, _)| column == name)
+                .map_or(0, |(_, value)| *value)
+                .to_string()
+        });
+        let file = OpenOptions::new().append(true).open(&self.report_path)?;
+        let mut writer = WriterBuilder::new().has_headers(false).from_writer(file);
+        writer.write_record(record)?;
         writer.flush()?;
 
         Ok(())
     }
 }
+
+/// Returns the name of the report column of the precompile named `name`.
+fn column_name(name: &str) -> String {
+    match name {
+        // the name of the column before all precompiles were annotated
+        "kzg-point-evaluation" => "kzg_point_eval_cycles".to_string(),
+        _ => format!("{}_cycles", name.replace('-', "_")),
+    }
+}
+
+/// Reads the header of the report, which is empty if there is no report yet.
+fn read_header(path: &Path) -> eyre::Result<Vec<String>> {
+    if !path.exists() || path.metadata()?.len() == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut reader = ReaderBuilder::new().from_path(path)?;
+    Ok(reader.headers()?.iter().map(String::from).collect())
+}
+
+/// Reads the rows of the report, which are empty if there is no report yet.
+fn read_records(path: &Path) -> eyre::Result<Vec<Vec<String>>> {
+    if !path.exists() || path.metadata()?.len() == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut reader = ReaderBuilder::new().from_path(path)?;
+    reader.records().map(|record| Ok(record?.iter().map(String::from).collect())).collect()
+}
@@ -8,8 +8,8 @@ use clap::Parser;
 use execute::PersistExecutionReport;
 use op_alloy_network::Optimism;
 use rsp_host_executor::{
-    build_executor, create_eth_block_execution_strategy_factory,
-    create_op_block_execution_strategy_factory, BlockExecutor,
+    aggregate, build_executor, create_eth_host_executor, create_op_host_executor, execute_range,
+    BlockExecutor,
 };
 use sp1_sdk::include_elf;
 use tracing_subscriber::{
@@ -43,34 +43,71 @@ async fn main() -> eyre::Result<()> {
         RpcClient::builder().layer(RetryBackoffLayer::new(3, 1000, 100)).http(rpc_url)
     });
 
-    if config.chain.is_optimism() {
+    if let (Some(last_block_number), true) = (args.last_block_number, args.aggregate) {
+        let Some(rpc_client) = rpc_client else {
+            eyre::bail!("--aggregate requires an RPC");
+        };
+        let aggregation_elf = include_elf!("rsp-aggregation");
+
+        if config.genesis.is_optimism() {
+            let elf = include_elf!("rsp-client-op");
+            let host_executor = create_op_host_executor(&config.genesis);
+            let provider = RootProvider::<Optimism>::new(rpc_client);
+
+            aggregate(
+                elf,
+                aggregation_elf,
+                provider,
+                host_executor,
+                &config,
+                block_number,
+                last_block_number,
+            )
+            .await?;
+        } else {
+            let elf = include_elf!("rsp-client");
+            let host_executor = create_eth_host_executor(&config.genesis);
+            let provider = RootProvider::<Ethereum>::new(rpc_client);
+
+            aggregate(
+                elf,
+                aggregation_elf,
+                provider,
+                host_executor,
+                &config,
+                block_number,
+                last_block_number,
+            )
+            .await?;
+        }
+    } else if let Some(last_block_number) = args.last_block_number {
+        if config.genesis.is_optimism() {
+            eyre::bail!("--last-block-number is only supported on Ethereum chains");
+        }
+        let Some(rpc_client) = rpc_client else {
+            eyre::bail!("--last-block-number requires an RPC");
+        };
+
+        let elf = include_elf!("rsp-client-range");
+        let provider = RootProvider::<Ethereum>::new(rpc_client);
+
+        execute_range(elf, provider, &config, block_number, last_block_number).await?;
+    } else if config.genesis.is_optimism() {
         let elf = include_elf!("rsp-client-op").to_vec();
-        let block_execution_strategy_factory =
-            create_op_block_execution_strategy_factory(&config.genesis);
+        let host_executor = create_op_host_executor(&config.genesis);
         let provider = rpc_client.map(RootProvider::<Optimism>::new);
 
-        let mut executor = build_executor(
-            elf,
-            provider,
-            block_execution_strategy_factory,
-            persist_execution_report,
-            config,
-        )?;
+        let mut executor =
+            build_executor(elf, provider, host_executor, persist_execution_report, config)?;
 
         executor.execute(block_number).await?;
     } else {
         let elf = include_elf!("rsp-client").to_vec();
-        let block_execution_strategy_factory =
-            create_eth_block_execution_strategy_factory(&config.genesis, config.custom_beneficiary);
+        let host_executor = create_eth_host_executor(&config.genesis);
         let provider = rpc_client.map(RootProvider::<Ethereum>::new);
 
-        let mut executor = build_executor(
-            elf,
-            provider,
-            block_execution_strategy_factory,
-            persist_execution_report,
-            config,
-        )?;
+        let mut executor =
+            build_executor(elf, provider, host_executor, persist_execution_report, config)?;
 
         executor.execute(block_number).await?;
     }
@@ -0,0 +1,87 @@
+//! Recovery of the signer of the blocks of chains using the Clique consensus (EIP-225).
+//!
+//! Clique is not implemented in reth. The main difference for execution is the block
+//! beneficiary: the header beneficiary is zero, and the block rewards and fees are credited to
+//! the signer of the block, whose signature is stored at the end of the header extra data.
+
+use alloy_consensus::{BlockHeader, Header};
+use alloy_primitives::{Address, Signature};
+use std::sync::{Arc, Mutex};
+
+/// The length of the signature at the end of the extra data.
+const EXTRA_SEAL: usize = 65;
+
+/// Recovers the signer of a Clique block from the signature in its extra data.
+///
+/// Returns `None` if the extra data does not end with a valid signature.
+pub fn recover_signer<H: BlockHeader>(header: &H) -> Option<Address> {
+    let signature = seal(header)?;
+    let extra_data = header.extra_data();
+    let seal_start = extra_data.len() - EXTRA_SEAL;
+
+    // The signature is over the hash of the header without the signature.
+    let seal_hash = Header {
+        parent_hash: header.parent_hash(),
+        ommers_hash: header.ommers_hash(),
+        beneficiary: header.beneficiary(),
+        state_root: header.state_root(),
+        transactions_root: header.transactions_root(),
+        receipts_root: header.receipts_root(),
+        logs_bloom: header.logs_bloom(),
+        difficulty: header.difficulty(),
+        number: header.number(),
+        gas_limit: header.gas_limit(),
+        gas_used: header.gas_used(),
+        timestamp: header.timestamp(),
+        extra_data: extra_data[..seal_start].to_vec().into(),
+        mix_hash: header.mix_hash().unwrap_or_default(),
+        nonce: header.nonce().unwrap_or_default(),
+        base_fee_per_gas: header.base_fee_per_gas(),
+        withdrawals_root: header.withdrawals_root(),
+        blob_gas_used: header.blob_gas_used(),
+        excess_blob_gas: header.excess_blob_gas(),
+        parent_beacon_block_root: header.parent_beacon_block_root(),
+        requests_hash: header.requests_hash(),
+    }
+    .hash_slow();
+
+    signature.recover_address_from_prehash(&seal_hash).ok()
+}
+
+/// The signer of the block being executed, shared by the executor, which recovers it once per
+/// block, and the EVM configuration, which builds the environment of the block several times.
+///
+/// Recovering the signer is expensive in the zkVM, so it is only done again if the environment
+/// is built for another block than the one recorded.
+#[derive(Debug, Clone, Default)]
+pub struct SignerCache(Arc<Mutex<Option<(u64, Signature, Address)>>>);
+
+impl SignerCache {
+    /// Recovers the signer of the block and records it.
+    ///
+    /// Returns `None` if the extra data does not end with a valid signature.
+    pub fn recover<H: BlockHeader>(&self, header: &H) -> Option<Address> {
+        let signer = recover_signer(header)?;
+        *self.0.lock().unwrap() = Some((header.number(), seal(header)?, signer));
+
+        Some(signer)
+    }
+
+    /// Returns the signer of the block, which is recovered if it is not the recorded one.
+    pub fn get<H: BlockHeader>(&self, header: &H) -> Option<Address> {
+        let seal = seal(header)?;
+        match *self.0.lock().unwrap() {
+            Some((number, recorded, signer)) if number == header.number() && recorded == seal => {
+                Some(signer)
+            }
+            _ => recover_signer(header),
+        }
+    }
+}
+
+/// Returns the signature at the end of the extra data of the header.
+fn seal<H: BlockHeader>(header: &H) -> Option<Signature> {
+    let extra_data = header.extra_data();
+    let seal_start = extra_data.len().checked_sub(EXTRA_SEAL)?;
+    Signature::from_raw(&extra_data[seal_start..]).ok()
+}
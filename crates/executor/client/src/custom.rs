@@ -5,104 +5,111 @@
 //! The [CustomEvmConfig] type implements the [ConfigureEvm] and [ConfigureEvmEnv] traits,
 //! configuring the custom CustomEvmConfig precompiles and instructions.
 
-use reth_chainspec::ChainSpec;
+use reth_chainspec::{ChainSpec, ForkCondition};
 use reth_evm::{ConfigureEvm, ConfigureEvmEnv, Database, EvmEnv, NextBlockEnvAttributes};
 use reth_evm_ethereum::{EthEvm, EthEvmConfig};
 use revm::{
     handler::register::{EvmHandler, HandleRegisters},
-    precompile::{
-        bn128, kzg_point_evaluation, secp256k1, Precompile, PrecompileResult, PrecompileSpecId,
-        PrecompileWithAddress,
-    },
-    ContextPrecompiles,
+    precompile::{u64_to_address, Precompile, PrecompileResult, StatefulPrecompile},
+    ContextPrecompile, ContextPrecompiles,
 };
 use revm_primitives::{Address, Bytes, EVMError, Env, HaltReason};
-use std::sync::Arc;
+use std::{borrow::Cow, sync::Arc};
+
+use crate::clique;
 
 pub type CustomEthEvmConfig = CustomEvmConfig<EthEvmConfig>;
 
 #[cfg(feature = "optimism")]
 pub type CustomOpEvmConfig = CustomEvmConfig<reth_optimism_evm::OpEvmConfig>;
 
-/// Create an annotated precompile that tracks the cycle count of a precompile.
+/// The names of the known precompiles, by address.
+///
+/// These names are used in the `cycle-tracker-report` annotations of the precompile calls, which
+/// the executor aggregates in the execution report as `precompile-{name}`.
+pub const PRECOMPILE_NAMES: &[(Address, &str)] = &[
+    (u64_to_address(0x01), "ecrecover"),
+    (u64_to_address(0x02), "sha256"),
+    (u64_to_address(0x03), "ripemd160"),
+    (u64_to_address(0x04), "identity"),
+    (u64_to_address(0x05), "modexp"),
+    (u64_to_address(0x06), "bn-add"),
+    (u64_to_address(0x07), "bn-mul"),
+    (u64_to_address(0x08), "bn-pair"),
+    (u64_to_address(0x09), "blake2f"),
+    (u64_to_address(0x0a), "kzg-point-evaluation"),
+    (u64_to_address(0x0b), "bls12-g1-add"),
+    (u64_to_address(0x0c), "bls12-g1-msm"),
+    (u64_to_address(0x0d), "bls12-g2-add"),
+    (u64_to_address(0x0e), "bls12-g2-msm"),
+    (u64_to_address(0x0f), "bls12-pairing"),
+    (u64_to_address(0x10), "bls12-map-fp-to-g1"),
+    (u64_to_address(0x11), "bls12-map-fp2-to-g2"),
+    (u64_to_address(0x100), "p256-verify"),
+];
+
+/// Returns the name of the precompile at `address`.
+///
+/// Precompiles missing from [PRECOMPILE_NAMES], e.g. the ones of a custom chain, are named after
+/// their address.
+pub fn precompile_name(address: &Address) -> Cow<'static, str> {
+    PRECOMPILE_NAMES
+        .iter()
+        .find(|(precompile, _)| precompile == address)
+        .map_or_else(|| Cow::Owned(address.to_string()), |(_, name)| Cow::Borrowed(*name))
+}
+
+/// A precompile that tracks the cycle count of the precompile it wraps.
 /// This is useful for tracking how many cycles in total are consumed by calls to a given
 /// precompile.
-macro_rules! create_annotated_precompile {
-    ($precompile:expr, $name:expr) => {
-        PrecompileWithAddress(
-            $precompile.0,
-            Precompile::Standard(|input: &Bytes, gas_limit: u64| -> PrecompileResult {
-                let precompile = $precompile.precompile();
-                match precompile {
-                    Precompile::Standard(precompile) => {
-                        println!(concat!("cycle-tracker-report-start: precompile-", $name));
-                        let result = precompile(input, gas_limit);
-                        println!(concat!("cycle-tracker-report-end: precompile-", $name));
-                        result
-                    }
-                    _ => panic!("Annotated precompile must be a standard precompile."),
-                }
-            }),
-        )
-    };
+struct AnnotatedPrecompile {
+    name: Cow<'static, str>,
+    inner: Precompile,
 }
 
-// An annotated version of the KZG point evaluation precompile. Because this is a stateful
-// precompile we cannot use the `create_annotated_precompile` macro
-pub(crate) const ANNOTATED_KZG_PROOF: PrecompileWithAddress = PrecompileWithAddress(
-    kzg_point_evaluation::POINT_EVALUATION.0,
-    Precompile::Env(|input: &Bytes, gas_limit: u64, env: &Env| -> PrecompileResult {
-        let precompile = kzg_point_evaluation::POINT_EVALUATION.precompile();
-        match precompile {
-            Precompile::Env(precompile) => {
-                println!(concat!(
-                    "cycle-tracker-report-start: precompile-",
-                    "kzg-point-evaluation"
-                ));
-                let result = precompile(input, gas_limit, env);
-                println!(concat!("cycle-tracker-report-end: precompile-", "kzg-point-evaluation"));
-                result
-            }
-            _ => panic!("Annotated precompile must be a env precompile."),
+impl StatefulPrecompile for AnnotatedPrecompile {
+    fn call(&self, bytes: &Bytes, gas_limit: u64, env: &Env) -> PrecompileResult {
+        println!("cycle-tracker-report-start: precompile-{}", self.name);
+        let result = self.inner.call_ref(bytes, gas_limit, env);
+        println!("cycle-tracker-report-end: precompile-{}", self.name);
+        result
+    }
+}
+
+/// Wraps every precompile with an [AnnotatedPrecompile].
+///
+/// The precompiles themselves are left untouched, so that the chain-specific implementations
+/// (e.g. the OP Stack input limits) are kept. Context precompiles, which have access to the
+/// state, are not annotated.
+fn annotate_precompiles<DB: Database>(
+    mut precompiles: ContextPrecompiles<DB>,
+) -> ContextPrecompiles<DB> {
+    for (address, precompile) in precompiles.to_mut().iter_mut() {
+        if let ContextPrecompile::Ordinary(inner) = precompile {
+            let annotated =
+                AnnotatedPrecompile { name: precompile_name(address), inner: inner.clone() };
+            *inner = Precompile::new_stateful(annotated);
         }
-    }),
-);
-
-pub(crate) const ANNOTATED_ECRECOVER: PrecompileWithAddress =
-    create_annotated_precompile!(secp256k1::ECRECOVER, "ecrecover");
-pub(crate) const ANNOTATED_BN_ADD: PrecompileWithAddress =
-    create_annotated_precompile!(bn128::add::ISTANBUL, "bn-add");
-pub(crate) const ANNOTATED_BN_MUL: PrecompileWithAddress =
-    create_annotated_precompile!(bn128::mul::ISTANBUL, "bn-mul");
-pub(crate) const ANNOTATED_BN_PAIR: PrecompileWithAddress =
-    create_annotated_precompile!(bn128::pair::ISTANBUL, "bn-pair");
+    }
+
+    precompiles
+}
 
 /// Sets the precompiles to the EVM handler
 ///
 /// This will be invoked when the EVM is created via [ConfigureEvm::evm] or
 /// [ConfigureEvm::evm_with_inspector]
 ///
-/// This will use the default mainnet precompiles and add additional precompiles.
+/// This will use the precompiles loaded by the chain at the current spec, and annotate them.
 fn set_precompiles<EXT, DB>(handler: &mut EvmHandler<'_, EXT, DB>)
 where
     DB: Database,
 {
-    // first we need the evm spec id, which determines the precompiles
-    let spec_id = handler.cfg.spec_id;
-    // install the precompiles
-    handler.pre_execution.load_precompiles = Arc::new(move || {
-        let mut loaded_precompiles: ContextPrecompiles<DB> =
-            ContextPrecompiles::new(PrecompileSpecId::from_spec_id(spec_id));
-        loaded_precompiles.extend(vec![
-            ANNOTATED_ECRECOVER,
-            ANNOTATED_BN_ADD,
-            ANNOTATED_BN_MUL,
-            ANNOTATED_BN_PAIR,
-            ANNOTATED_KZG_PROOF,
-        ]);
-
-        loaded_precompiles
-    });
+    // the precompiles of the chain, which are installed by the handler registers of the chain
+    // before this one
+    let load_precompiles = handler.pre_execution.load_precompiles.clone();
+    handler.pre_execution.load_precompiles =
+        Arc::new(move || annotate_precompiles(load_precompiles()));
 }
 
 /// Custom EVM configuration
@@ -110,16 +117,41 @@ where
 pub struct CustomEvmConfig<C> {
     evm_config: C,
 
-    // Some chains uses Clique consensus, which is not implemented in Reth.
-    // The main difference for execution is the block beneficiary: Reth will
-    // credit the block reward to the beneficiary address, whereas in Clique,
-    // the reward is credited to the signer.
-    custom_beneficiary: Option<Address>,
+    /// The signers of the blocks if the chain uses the Clique consensus, in which case the block
+    /// rewards and fees are credited to the signer of the block, see [crate::clique].
+    clique: Option<clique::SignerCache>,
+
+    /// Whether the precompile calls are annotated with their cycle count, which is only
+    /// meaningful in the zkVM.
+    annotate_precompiles: bool,
+
+    /// The activation of OP Isthmus, from which the operator fee is charged to the transactions,
+    /// see [crate::optimism::OperatorFee]. Never active on other chains.
+    isthmus: ForkCondition,
 }
 
 impl CustomEvmConfig<EthEvmConfig> {
-    pub fn eth(chain_spec: Arc<ChainSpec>, custom_beneficiary: Option<Address>) -> Self {
-        Self { evm_config: EthEvmConfig::new(chain_spec), custom_beneficiary }
+    pub fn eth(chain_spec: Arc<ChainSpec>) -> Self {
+        Self {
+            clique: chain_spec.genesis.config.clique.is_some().then(Default::default),
+            evm_config: EthEvmConfig::new(chain_spec),
+            annotate_precompiles: false,
+            isthmus: ForkCondition::Never,
+        }
+    }
+}
+
+impl<C> CustomEvmConfig<C> {
+    /// Sets whether the precompile calls are annotated with their cycle count, which they are not
+    /// by default.
+    pub fn with_precompile_annotations(mut self, annotate_precompiles: bool) -> Self {
+        self.annotate_precompiles = annotate_precompiles;
+        self
+    }
+
+    /// Returns the cache of the block signers, if the chain uses the Clique consensus.
+    pub fn clique_signers(&self) -> Option<&clique::SignerCache> {
+        self.clique.as_ref()
     }
 }
 
@@ -127,8 +159,10 @@ impl CustomEvmConfig<EthEvmConfig> {
 impl CustomEvmConfig<reth_optimism_evm::OpEvmConfig> {
     pub fn optimism(chain_spec: Arc<reth_optimism_chainspec::OpChainSpec>) -> Self {
         Self {
+            isthmus: chain_spec.fork(reth_optimism_forks::OpHardfork::Isthmus),
             evm_config: reth_optimism_evm::OpEvmConfig::new(chain_spec),
-            custom_beneficiary: None,
+            clique: None,
+            annotate_precompiles: false,
         }
     }
 }
@@ -144,7 +178,9 @@ impl ConfigureEvm for CustomEvmConfig<EthEvmConfig> {
         evm_env: EvmEnv<Self::Spec>,
     ) -> Self::Evm<'_, DB, ()> {
         let mut evm = self.evm_config.evm_with_env(db, evm_env);
-        evm.handler.append_handler_register(HandleRegisters::Plain(set_precompiles));
+        if self.annotate_precompiles {
+            evm.handler.append_handler_register(HandleRegisters::Plain(set_precompiles));
+        }
         evm
     }
 
@@ -159,7 +195,9 @@ impl ConfigureEvm for CustomEvmConfig<EthEvmConfig> {
         I: revm::GetInspector<DB>,
     {
         let mut evm = self.evm_config.evm_with_env_and_inspector(db, evm_env, inspector);
-        evm.handler.append_handler_register(HandleRegisters::Plain(set_precompiles));
+        if self.annotate_precompiles {
+            evm.handler.append_handler_register(HandleRegisters::Plain(set_precompiles));
+        }
         evm
     }
 }
@@ -175,8 +213,16 @@ impl ConfigureEvm for CustomEvmConfig<reth_optimism_evm::OpEvmConfig> {
         db: DB,
         evm_env: EvmEnv<Self::Spec>,
     ) -> Self::Evm<'_, DB, ()> {
+        let isthmus = self.isthmus.active_at_timestamp(evm_env.block_env.timestamp.saturating_to());
         let mut evm = self.evm_config.evm_with_env(db, evm_env);
-        evm.handler.append_handler_register(HandleRegisters::Plain(set_precompiles));
+        if self.annotate_precompiles {
+            evm.handler.append_handler_register(HandleRegisters::Plain(set_precompiles));
+        }
+        if isthmus {
+            evm.handler.append_handler_register(HandleRegisters::Plain(
+                crate::optimism::operator_fee_handler_register,
+            ));
+        }
         evm
     }
 
@@ -190,8 +236,16 @@ impl ConfigureEvm for CustomEvmConfig<reth_optimism_evm::OpEvmConfig> {
         DB: reth_evm::Database,
         I: revm::GetInspector<DB>,
     {
+        let isthmus = self.isthmus.active_at_timestamp(evm_env.block_env.timestamp.saturating_to());
         let mut evm = self.evm_config.evm_with_env_and_inspector(db, evm_env, inspector);
-        evm.handler.append_handler_register(HandleRegisters::Plain(set_precompiles));
+        if self.annotate_precompiles {
+            evm.handler.append_handler_register(HandleRegisters::Plain(set_precompiles));
+        }
+        if isthmus {
+            evm.handler.append_handler_register(HandleRegisters::Plain(
+                crate::optimism::operator_fee_handler_register,
+            ));
+        }
         evm
     }
 }
@@ -210,8 +264,11 @@ impl<C: ConfigureEvmEnv> ConfigureEvmEnv for CustomEvmConfig<C> {
     fn evm_env(&self, header: &Self::Header) -> EvmEnv<Self::Spec> {
         let mut evm_env = self.evm_config.evm_env(header);
 
-        if let Some(beneficiary) = self.custom_beneficiary {
-            evm_env.block_env.coinbase = beneficiary;
+        if let Some(signers) = &self.clique {
+            // An incorrectly signed block is rejected by the executor.
+            if let Some(signer) = signers.get(header) {
+                evm_env.block_env.coinbase = signer;
+            }
         }
 
         evm_env
@@ -225,3 +282,72 @@ impl<C: ConfigureEvmEnv> ConfigureEvmEnv for CustomEvmConfig<C> {
         self.evm_config.next_evm_env(parent, attributes)
     }
 }
+
+#[cfg(all(test, feature = "optimism"))]
+mod tests {
+    use revm::{
+        db::EmptyDB,
+        precompile::{bn128, secp256r1, PrecompileErrors, PrecompileSpecId, Precompiles},
+        primitives::{HandlerCfg, SpecId},
+    };
+
+    use super::*;
+
+    /// Loads the annotated precompiles of an OP Stack chain at `spec_id`.
+    fn op_precompiles(spec_id: SpecId) -> ContextPrecompiles<EmptyDB> {
+        let mut handler =
+            EvmHandler::<(), EmptyDB>::new(HandlerCfg::new_with_optimism(spec_id, true));
+        set_precompiles(&mut handler);
+        (handler.pre_execution.load_precompiles)()
+    }
+
+    fn call(
+        precompiles: &mut ContextPrecompiles<EmptyDB>,
+        address: Address,
+        input: Vec<u8>,
+    ) -> PrecompileResult {
+        match &precompiles.to_mut()[&address] {
+            ContextPrecompile::Ordinary(precompile) => {
+                assert!(matches!(precompile, Precompile::Stateful(_)), "not annotated");
+                precompile.call_ref(&input.into(), u64::MAX, &Env::default())
+            }
+            _ => panic!("unexpected context precompile"),
+        }
+    }
+
+    #[test]
+    fn test_precompile_names() {
+        for address in Precompiles::new(PrecompileSpecId::PRAGUE).addresses() {
+            assert!(PRECOMPILE_NAMES.iter().any(|(precompile, _)| precompile == address));
+        }
+        assert_eq!(precompile_name(&secp256r1::P256VERIFY.0), "p256-verify");
+
+        let custom = Address::repeat_byte(0x42);
+        assert_eq!(precompile_name(&custom), custom.to_string());
+    }
+
+    #[test]
+    fn test_op_p256_verify() {
+        let p256_verify = secp256r1::P256VERIFY.0;
+        assert!(!op_precompiles(SpecId::ECOTONE).contains(&p256_verify));
+
+        for spec_id in [SpecId::FJORD, SpecId::GRANITE] {
+            // an invalid signature returns an empty output
+            let output = call(&mut op_precompiles(spec_id), p256_verify, vec![0; 160]).unwrap();
+            assert!(output.bytes.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_op_bn_pair_input_limit() {
+        // 600 pairs of points at infinity, above the limit of 112687 bytes introduced by Granite
+        let input = vec![0; 600 * 192];
+        let bn_pair = bn128::pair::ISTANBUL.0;
+
+        let output = call(&mut op_precompiles(SpecId::FJORD), bn_pair, input.clone()).unwrap();
+        assert_eq!(output.bytes[31], 1);
+
+        let result = call(&mut op_precompiles(SpecId::GRANITE), bn_pair, input);
+        assert!(matches!(result, Err(PrecompileErrors::Error(_))));
+    }
+}
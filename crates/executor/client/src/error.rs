@@ -1,7 +1,7 @@
-use alloy_primitives::{Address, FixedBytes};
+use alloy_primitives::{Address, FixedBytes, B256};
 use reth_consensus::ConsensusError;
 use reth_evm::execute::BlockExecutionError;
-use rsp_mpt::Error as MptError;
+use rsp_mpt::{Error as MptError, StateUpdateError};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ClientError {
@@ -9,6 +9,14 @@ pub enum ClientError {
     SignatureRecoveryFailed,
     #[error("Mismatched state root after executing the block")]
     MismatchedStateRoot,
+    #[error("Mismatched transactions root \n computed: {}, header: {}", .0, .1)]
+    MismatchedTransactionsRoot(B256, B256),
+    #[error("Mismatched ommers hash \n computed: {}, header: {}", .0, .1)]
+    MismatchedOmmersHash(B256, B256),
+    #[error("Mismatched withdrawals root \n computed: {:?}, header: {:?}", .0, .1)]
+    MismatchedWithdrawalsRoot(Option<B256>, Option<B256>),
+    #[error("The block body must have an empty list of withdrawals from Isthmus on")]
+    UnexpectedWithdrawals,
     #[error("unknown chain ID: {}", .0)]
     UnknownChainId(u64),
     #[error("Missing bytecode for account {}", .0)]
@@ -19,10 +27,24 @@ pub enum ClientError {
     InvalidHeaderBlockNumber(u64, u64),
     #[error("Invalid parent header found for block \n expected: {}, found: {}", .0, .1)]
     InvalidHeaderParentHash(FixedBytes<32>, FixedBytes<32>),
+    #[error("Invalid block header: {}", .0)]
+    InvalidHeader(ConsensusError),
+    #[error("The range of blocks to execute is empty")]
+    EmptyBlockRange,
+    #[error("Unsupported public values version {}", .0)]
+    UnsupportedPublicValuesVersion(u8),
+    #[error("Block {} is not from the chain of the first block", .0)]
+    MismatchedChain(u64),
+    #[error("Invalid Clique signature in the header extra data")]
+    InvalidCliqueSeal,
+    #[error("Mismatched block beneficiary \n supplied: {}, block: {}", .0, .1)]
+    MismatchedBeneficiary(Address, Address),
     #[error("Failed to validate post exectution state {}", 0)]
     PostExecutionError(#[from] ConsensusError),
     #[error("Block Execution Failed: {}", .0)]
     BlockExecutionError(#[from] BlockExecutionError),
+    #[error("Failed to update the state trie: {}", .0)]
+    StateUpdateError(#[from] StateUpdateError),
     #[error("Mpt Error: {}", .0)]
     MptError(#[from] MptError),
     #[error("Failed to read the genesis file: {}", .0)]
@@ -1,19 +1,31 @@
-use std::sync::Arc;
+use std::{mem, sync::Arc};
 
-use alloy_consensus::{BlockHeader, Header, TxReceipt};
-use alloy_primitives::Bloom;
-use reth_chainspec::ChainSpec;
+use alloy_consensus::{
+    proofs::{calculate_ommers_root, calculate_transaction_root, calculate_withdrawals_root},
+    BlockHeader, Header, TxReceipt,
+};
+use alloy_eips::eip2718::Encodable2718;
+use alloy_primitives::{Address, Bloom, B256};
+use reth_chainspec::{ChainSpec, ForkCondition};
+use reth_consensus::{ConsensusError, HeaderValidator};
+use reth_ethereum_consensus::EthBeaconConsensus;
 use reth_evm::execute::{BlockExecutionStrategy, BlockExecutionStrategyFactory};
 use reth_evm_ethereum::execute::EthExecutionStrategyFactory;
 use reth_execution_types::ExecutionOutcome;
-use reth_primitives_traits::Block;
+use reth_primitives_traits::{Block, SealedHeader};
 use reth_trie::KeccakKeyHasher;
-use revm::db::{states::bundle_state::BundleRetention, WrapDatabaseRef};
-use revm_primitives::Address;
+use revm::db::{states::bundle_state::BundleRetention, BundleState, State, WrapDatabaseRef};
+use rsp_mpt::StateCommitment;
+use rsp_primitives::genesis::chain_config_hash;
 
 use crate::{
-    custom::CustomEthEvmConfig, error::ClientError, into_primitives::FromInput,
-    io::ClientExecutorInput,
+    clique,
+    custom::CustomEthEvmConfig,
+    error::ClientError,
+    into_primitives::FromInput,
+    io::{ClientExecutorInput, ClientExecutorRangeInput},
+    optimism,
+    public_values::{PublicValues, RangePublicValues},
 };
 
 pub type EthClientExecutor = ClientExecutor<EthExecutionStrategyFactory<CustomEthEvmConfig>>;
@@ -27,10 +39,26 @@ pub type OpClientExecutor = ClientExecutor<
     >,
 >;
 
+/// The maximum size of the extra data of a header, see the yellow paper.
+const MAXIMUM_EXTRA_DATA_SIZE: usize = 32;
+
 /// An executor that executes a block inside a zkVM.
 #[derive(Debug, Clone)]
 pub struct ClientExecutor<F: BlockExecutionStrategyFactory> {
     block_execution_strategy_factory: F,
+    consensus: Arc<dyn HeaderValidator>,
+    /// The id of the chain, committed to by the public values.
+    chain_id: u64,
+    /// The hash of the rules of the chain, committed to by the public values, see
+    /// [chain_config_hash].
+    chain_config_hash: B256,
+    /// The signers of the blocks if the chain uses Clique, whose headers carry the block
+    /// signature in their extra data. Shared with the EVM configuration, so that each signer is
+    /// only recovered once.
+    clique: Option<clique::SignerCache>,
+    /// The activation of OP Isthmus, from which the withdrawals root is the storage root of the
+    /// L2ToL1MessagePasser. Never active on other chains.
+    isthmus: ForkCondition,
 }
 
 impl<F> ClientExecutor<F>
@@ -38,16 +66,59 @@ where
     F: BlockExecutionStrategyFactory,
     F::Primitives: FromInput,
 {
-    pub fn execute(
+    pub fn execute<S: StateCommitment>(
         &self,
-        mut input: ClientExecutorInput<F::Primitives>,
+        input: ClientExecutorInput<F::Primitives, S>,
     ) -> Result<Header, ClientError> {
+        self.execute_block(input, false).map(|(header, _)| header)
+    }
+
+    /// Executes the block and returns the [PublicValues] committing to it.
+    ///
+    /// The `input_hash` must be the [PublicValues::hash_input] of the serialized `input`.
+    pub fn execute_with_public_values<S: StateCommitment>(
+        &self,
+        input: ClientExecutorInput<F::Primitives, S>,
+        input_hash: B256,
+    ) -> Result<PublicValues, ClientError> {
+        let header = self.execute(input)?;
+
+        Ok(PublicValues::new(self.chain_id, self.chain_config_hash, input_hash, &header))
+    }
+
+    /// Executes the block and returns its header, along with the storage root of the OP Stack
+    /// `L2ToL1MessagePasser` after the block if `message_passer` is set.
+    fn execute_block<S: StateCommitment>(
+        &self,
+        input: ClientExecutorInput<F::Primitives, S>,
+        message_passer: bool,
+    ) -> Result<(Header, Option<B256>), ClientError> {
+        // From OP Isthmus on, the withdrawals root is the storage root of the
+        // L2ToL1MessagePasser, which is checked after execution.
+        let isthmus = self.isthmus.active_at_timestamp(input.current_block.header.timestamp);
+
+        // Validate the block body against the header.
+        profile!("validate block body", { validate_block_body(&input.current_block, isthmus) })?;
+
         // Initialize the witnessed database with verified storage proofs.
         let db = profile!("initialize witness db", {
             let trie_db = input.witness_db().unwrap();
             WrapDatabaseRef(trie_db)
         });
 
+        // Validate the header against the parent header.
+        profile!("validate header", {
+            self.validate_header(&input.current_block.header, input.parent_header())
+        })?;
+
+        // Check the supplied beneficiary against the one the block is executed with.
+        let coinbase = self.coinbase(&input.current_block.header)?;
+        if let Some(beneficiary) = input.custom_beneficiary {
+            if beneficiary != coinbase {
+                return Err(ClientError::MismatchedBeneficiary(beneficiary, coinbase));
+            }
+        }
+
         let mut strategy = self.block_execution_strategy_factory.create_strategy(db);
 
         let block = profile!("recover senders", {
@@ -78,6 +149,11 @@ where
             })
         });
 
+        // The requests hash was checked against the header by `validate_block_post_execution`,
+        // it is only committed to from Prague (EIP-7685).
+        let requests_hash =
+            input.current_block.header().requests_hash().map(|_| requests.requests_hash());
+
         // Convert the output to an execution outcome.
         let executor_outcome = ExecutionOutcome::new(
             state.take_bundle(),
@@ -87,52 +163,249 @@ where
         );
 
         // Verify the state root.
-        let state_root = profile!("compute state root", {
-            input.parent_state.update(&executor_outcome.hash_state_slow::<KeccakKeyHasher>());
-            input.parent_state.state_root()
-        });
+        let (state_root, message_passer_storage_root) = profile_report!("compute-state-root", {
+            let hashed_post_state = executor_outcome.hash_state_slow::<KeccakKeyHasher>();
+            if isthmus || message_passer {
+                let mut parent_state = input.parent_state;
+                parent_state.update(&hashed_post_state).map_err(ClientError::from).and_then(|_| {
+                    let storage_root = optimism::message_passer_storage_root(&parent_state)?;
+                    Ok((parent_state.state_root(), Some(storage_root)))
+                })
+            } else {
+                input
+                    .parent_state
+                    .into_state_root(&hashed_post_state)
+                    .map(|state_root| (state_root, None))
+                    .map_err(ClientError::from)
+            }
+        })?;
+        let withdrawals_root = if isthmus {
+            message_passer_storage_root
+        } else {
+            input.current_block.header().withdrawals_root()
+        };
 
         if state_root != input.current_block.header().state_root() {
             return Err(ClientError::MismatchedStateRoot);
         }
 
+        if withdrawals_root != input.current_block.header().withdrawals_root() {
+            return Err(ClientError::MismatchedWithdrawalsRoot(
+                withdrawals_root,
+                input.current_block.header().withdrawals_root(),
+            ));
+        }
+
         // Derive the block header.
-        // Note: the receipts root and gas used are verified by `validate_block_post_execution`.
-        let header = Header {
-            parent_hash: input.current_block.header().parent_hash(),
-            ommers_hash: input.current_block.header().ommers_hash(),
-            beneficiary: input.current_block.header().beneficiary(),
+        let header = derive_header(
+            input.current_block.header(),
             state_root,
-            transactions_root: input.current_block.header().transactions_root(),
-            receipts_root: input.current_block.header().receipts_root(),
             logs_bloom,
-            difficulty: input.current_block.header().difficulty(),
-            number: input.current_block.header().number(),
-            gas_limit: input.current_block.header().gas_limit(),
-            gas_used: input.current_block.header().gas_used(),
-            timestamp: input.current_block.header().timestamp(),
-            extra_data: input.current_block.header().extra_data().clone(),
-            mix_hash: input.current_block.header().mix_hash().unwrap(),
-            nonce: input.current_block.header().nonce().unwrap(),
-            base_fee_per_gas: input.current_block.header().base_fee_per_gas(),
-            withdrawals_root: input.current_block.header().withdrawals_root(),
-            blob_gas_used: input.current_block.header().blob_gas_used(),
-            excess_blob_gas: input.current_block.header().excess_blob_gas(),
-            parent_beacon_block_root: input.current_block.header().parent_beacon_block_root(),
-            requests_hash: None,
-        };
+            withdrawals_root,
+            requests_hash,
+        );
 
-        Ok(header)
+        Ok((header, message_passer_storage_root))
+    }
+
+    /// Executes a range of consecutive blocks against a single witness and returns their
+    /// headers.
+    ///
+    /// Each block is executed like a single block, by an execution strategy of its own on top of
+    /// the changes of the previous blocks, and its state root is checked after applying its
+    /// changes to the state.
+    pub fn execute_range<S: StateCommitment + Clone>(
+        &self,
+        input: ClientExecutorRangeInput<F::Primitives, S>,
+    ) -> Result<Vec<Header>, ClientError> {
+        if input.blocks.is_empty() {
+            return Err(ClientError::EmptyBlockRange);
+        }
+
+        // Initialize the witnessed database with verified storage proofs, which also checks that
+        // the blocks are consecutive.
+        let trie_db = profile!("initialize witness db", { input.witness_db().unwrap() });
+
+        // The accounts and storage slots that are not modified by the blocks are read from the
+        // witness, while the state is updated after each block to check its state root.
+        let mut state = input.parent_state.clone();
+        let mut prestate = BundleState::default();
+        let mut headers = Vec::with_capacity(input.blocks.len());
+
+        for (i, current_block) in input.blocks.iter().enumerate() {
+            let parent_header = headers.last().unwrap_or(input.parent_header());
+            let isthmus = self.isthmus.active_at_timestamp(current_block.header.timestamp);
+
+            // Validate the block body and the header against the parent header.
+            profile!("validate block body", { validate_block_body(current_block, isthmus) })?;
+            profile!("validate header", {
+                self.validate_header(&current_block.header, parent_header)
+            })?;
+
+            // Check the supplied beneficiary against the one the block is executed with.
+            let coinbase = self.coinbase(&current_block.header)?;
+            if let Some(beneficiary) = input.custom_beneficiary {
+                if beneficiary != coinbase {
+                    return Err(ClientError::MismatchedBeneficiary(beneficiary, coinbase));
+                }
+            }
+
+            // The strategy reads the changes of the previous blocks before the witness.
+            let mut db = State::builder()
+                .with_database(WrapDatabaseRef(&trie_db))
+                .with_bundle_prestate(mem::take(&mut prestate))
+                .build();
+            let mut strategy = self.block_execution_strategy_factory.create_strategy(&mut db);
+
+            let block = profile!("recover senders", {
+                F::Primitives::from_input_block(current_block.clone())
+                    .try_into_recovered()
+                    .map_err(|_| ClientError::SignatureRecoveryFailed)
+            })?;
+
+            strategy.apply_pre_execution_changes(&block)?;
+
+            let executor_output = profile!("execute", { strategy.execute_transactions(&block) })?;
+
+            let requests =
+                strategy.apply_post_execution_changes(&block, &executor_output.receipts)?;
+
+            // Validate the block post execution.
+            profile!("validate block post-execution", {
+                strategy.validate_block_post_execution(&block, &executor_output.receipts, &requests)
+            })?;
+
+            // Take the changes of the block, and add them to the ones of the previous blocks.
+            let mut block_state = strategy.into_state();
+            block_state.merge_transitions(BundleRetention::Reverts);
+            let bundle = block_state.take_bundle();
+            prestate = db.take_bundle();
+            prestate.extend(bundle.clone());
+
+            // Accumulate the logs bloom.
+            let mut logs_bloom = Bloom::default();
+            executor_output.receipts.iter().for_each(|r| {
+                logs_bloom.accrue_bloom(&r.bloom());
+            });
+
+            let requests_hash =
+                current_block.header().requests_hash().map(|_| requests.requests_hash());
+
+            let executor_outcome = ExecutionOutcome::new(
+                bundle,
+                vec![executor_output.receipts],
+                current_block.header().number(),
+                vec![requests],
+            );
+
+            // Verify the state root.
+            profile_report!("compute-state-root", {
+                let hashed_post_state = executor_outcome.hash_state_slow::<KeccakKeyHasher>();
+                state.update(&hashed_post_state)
+            })?;
+            let state_root = state.state_root();
+            if state_root != current_block.header().state_root() {
+                return Err(ClientError::MismatchedStateRoot);
+            }
+
+            let withdrawals_root = if isthmus {
+                Some(optimism::message_passer_storage_root(&state)?)
+            } else {
+                current_block.header().withdrawals_root()
+            };
+            if withdrawals_root != current_block.header().withdrawals_root() {
+                return Err(ClientError::MismatchedWithdrawalsRoot(
+                    withdrawals_root,
+                    current_block.header().withdrawals_root(),
+                ));
+            }
+
+            let header = derive_header(
+                current_block.header(),
+                state_root,
+                logs_bloom,
+                withdrawals_root,
+                requests_hash,
+            );
+
+            // Link the derived header to the next block.
+            if let Some(next_block) = input.blocks.get(i + 1) {
+                let hash = header.hash_slow();
+                if hash != next_block.header.parent_hash {
+                    return Err(ClientError::InvalidHeaderParentHash(
+                        hash,
+                        next_block.header.parent_hash,
+                    ));
+                }
+            }
+
+            headers.push(header);
+        }
+
+        Ok(headers)
+    }
+
+    /// Executes a range of consecutive blocks and returns the [RangePublicValues] committing to
+    /// them.
+    ///
+    /// The `input_hash` must be the [PublicValues::hash_input] of the serialized `input`.
+    pub fn execute_range_with_public_values<S: StateCommitment + Clone>(
+        &self,
+        input: ClientExecutorRangeInput<F::Primitives, S>,
+        input_hash: B256,
+    ) -> Result<RangePublicValues, ClientError> {
+        let headers = self.execute_range(input)?;
+
+        Ok(RangePublicValues::new(self.chain_id, self.chain_config_hash, input_hash, &headers))
+    }
+
+    /// Returns the address credited with the block rewards and fees, recording the signer of the
+    /// block on Clique chains so that the EVM configuration does not recover it again.
+    fn coinbase(&self, header: &Header) -> Result<Address, ClientError> {
+        match &self.clique {
+            Some(signers) => signers.recover(header).ok_or(ClientError::InvalidCliqueSeal),
+            None => Ok(header.beneficiary),
+        }
+    }
+
+    /// Validates the header against the consensus rules and the parent header.
+    ///
+    /// The parent must have been checked to be the parent of the header, which is done when
+    /// building the witness db.
+    fn validate_header(&self, header: &Header, parent: &Header) -> Result<(), ClientError> {
+        let parent = SealedHeader::new(parent.clone(), header.parent_hash);
+        let header = SealedHeader::seal_slow(header.clone());
+
+        self.consensus.validate_header(&header).map_err(ClientError::InvalidHeader)?;
+        self.consensus
+            .validate_header_against_parent(&header, &parent)
+            .map_err(ClientError::InvalidHeader)?;
+
+        let len = header.extra_data.len();
+        if self.clique.is_none() && len > MAXIMUM_EXTRA_DATA_SIZE {
+            return Err(ClientError::InvalidHeader(ConsensusError::ExtraDataExceedsMax { len }));
+        }
+
+        Ok(())
     }
 }
 
 impl EthClientExecutor {
-    pub fn eth(chain_spec: Arc<ChainSpec>, custom_beneficiary: Option<Address>) -> Self {
+    pub fn eth(chain_spec: Arc<ChainSpec>) -> Self {
+        let evm_config = CustomEthEvmConfig::eth(chain_spec.clone())
+            .with_precompile_annotations(cfg!(feature = "precompile-annotations"));
+        let clique = evm_config.clique_signers().cloned();
+
         Self {
             block_execution_strategy_factory: EthExecutionStrategyFactory::new(
                 chain_spec.clone(),
-                CustomEthEvmConfig::eth(chain_spec, custom_beneficiary),
+                evm_config,
             ),
+            consensus: Arc::new(EthBeaconConsensus::new(chain_spec.clone())),
+            chain_id: chain_spec.chain.id(),
+            chain_config_hash: chain_config_hash(&chain_spec),
+            clique,
+            isthmus: ForkCondition::Never,
         }
     }
 }
@@ -143,9 +416,119 @@ impl OpClientExecutor {
         Self {
             block_execution_strategy_factory: reth_optimism_evm::OpExecutionStrategyFactory::new(
                 chain_spec.clone(),
-                crate::custom::CustomOpEvmConfig::optimism(chain_spec),
+                crate::custom::CustomOpEvmConfig::optimism(chain_spec.clone())
+                    .with_precompile_annotations(cfg!(feature = "precompile-annotations")),
                 reth_optimism_evm::BasicOpReceiptBuilder::default(),
             ),
+            consensus: Arc::new(reth_optimism_consensus::OpBeaconConsensus::new(
+                chain_spec.clone(),
+            )),
+            chain_id: chain_spec.inner.chain.id(),
+            chain_config_hash: chain_config_hash(&chain_spec.inner),
+            clique: None,
+            isthmus: chain_spec.fork(reth_optimism_forks::OpHardfork::Isthmus),
         }
     }
+
+    /// Executes the block and returns the [PublicValues] committing to it, along with its
+    /// [OutputRoot](optimism::OutputRoot).
+    ///
+    /// The `input_hash` must be the [PublicValues::hash_input] of the serialized `input`, and the
+    /// account of the `L2ToL1MessagePasser` must be part of the witness.
+    pub fn execute_with_output_root<S: StateCommitment>(
+        &self,
+        input: ClientExecutorInput<reth_optimism_primitives::OpPrimitives, S>,
+        input_hash: B256,
+    ) -> Result<(PublicValues, optimism::OutputRoot), ClientError> {
+        let (header, Some(message_passer_storage_root)) = self.execute_block(input, true)? else {
+            unreachable!("the storage root of the message passer is computed when requested")
+        };
+
+        let public_values =
+            PublicValues::new(self.chain_id, self.chain_config_hash, input_hash, &header);
+        let output_root = optimism::OutputRoot {
+            state_root: header.state_root,
+            message_passer_storage_root,
+            block_hash: public_values.block_hash,
+        };
+
+        Ok((public_values, output_root))
+    }
+}
+
+/// Derives the header of an executed block from the results of its execution.
+///
+/// Note: the receipts root and gas used are verified by `validate_block_post_execution`, and the
+/// transactions root, ommers hash and withdrawals root by `validate_block_body`.
+fn derive_header(
+    header: &Header,
+    state_root: B256,
+    logs_bloom: Bloom,
+    withdrawals_root: Option<B256>,
+    requests_hash: Option<B256>,
+) -> Header {
+    Header {
+        parent_hash: header.parent_hash(),
+        ommers_hash: header.ommers_hash(),
+        beneficiary: header.beneficiary(),
+        state_root,
+        transactions_root: header.transactions_root(),
+        receipts_root: header.receipts_root(),
+        logs_bloom,
+        difficulty: header.difficulty(),
+        number: header.number(),
+        gas_limit: header.gas_limit(),
+        gas_used: header.gas_used(),
+        timestamp: header.timestamp(),
+        extra_data: header.extra_data().clone(),
+        mix_hash: header.mix_hash().unwrap(),
+        nonce: header.nonce().unwrap(),
+        base_fee_per_gas: header.base_fee_per_gas(),
+        withdrawals_root,
+        blob_gas_used: header.blob_gas_used(),
+        excess_blob_gas: header.excess_blob_gas(),
+        parent_beacon_block_root: header.parent_beacon_block_root(),
+        requests_hash,
+    }
+}
+
+/// Checks that the header commits to the transactions, ommers and withdrawals of the block body.
+///
+/// From OP Isthmus on, the withdrawals root commits to the state instead of the block body.
+fn validate_block_body<T: Encodable2718>(
+    block: &alloy_consensus::Block<T>,
+    isthmus: bool,
+) -> Result<(), ClientError> {
+    let transactions_root = calculate_transaction_root(&block.body.transactions);
+    if transactions_root != block.header.transactions_root {
+        return Err(ClientError::MismatchedTransactionsRoot(
+            transactions_root,
+            block.header.transactions_root,
+        ));
+    }
+
+    let ommers_hash = calculate_ommers_root(&block.body.ommers);
+    if ommers_hash != block.header.ommers_hash {
+        return Err(ClientError::MismatchedOmmersHash(ommers_hash, block.header.ommers_hash));
+    }
+
+    // From Isthmus on, the withdrawals root of the header is the storage root of the
+    // L2ToL1MessagePasser, which is checked after execution, and the body has no withdrawals.
+    if isthmus {
+        if !block.body.withdrawals.as_ref().is_some_and(|withdrawals| withdrawals.is_empty()) {
+            return Err(ClientError::UnexpectedWithdrawals);
+        }
+        return Ok(());
+    }
+
+    let withdrawals_root =
+        block.body.withdrawals.as_ref().map(|withdrawals| calculate_withdrawals_root(withdrawals));
+    if withdrawals_root != block.header.withdrawals_root {
+        return Err(ClientError::MismatchedWithdrawalsRoot(
+            withdrawals_root,
+            block.header.withdrawals_root,
+        ));
+    }
+
+    Ok(())
 }
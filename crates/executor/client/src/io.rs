@@ -3,22 +3,42 @@ use std::iter::once;
 use alloy_consensus::{Block, BlockHeader, Header};
 use alloy_primitives::map::HashMap;
 use itertools::Itertools;
-use reth_errors::ProviderError;
+use reth_errors::{DatabaseError, ProviderError};
 use reth_primitives::{EthPrimitives, NodePrimitives};
-use reth_trie::TrieAccount;
 use revm::DatabaseRef;
-use revm_primitives::{keccak256, AccountInfo, Address, Bytecode, B256, U256};
-use rsp_mpt::EthereumState;
+use revm_primitives::{AccountInfo, Address, Bytecode, B256, U256};
+pub use rsp_mpt::NodeStore;
+use rsp_mpt::{EthereumState, StateCommitment};
 use rsp_primitives::genesis::Genesis;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
 use crate::error::ClientError;
 
-pub type EthClientExecutorInput = ClientExecutorInput<EthPrimitives>;
+pub type EthClientExecutorInput<S = EthereumState> = ClientExecutorInput<EthPrimitives, S>;
 
 #[cfg(feature = "optimism")]
-pub type OpClientExecutorInput = ClientExecutorInput<reth_optimism_primitives::OpPrimitives>;
+pub type OpClientExecutorInput<S = EthereumState> =
+    ClientExecutorInput<reth_optimism_primitives::OpPrimitives, S>;
+
+pub type EthClientExecutorRangeInput<S = EthereumState> =
+    ClientExecutorRangeInput<EthPrimitives, S>;
+
+/// The state the client programs rebuild from the [NodeStore] in which they read the witness.
+///
+/// With the `arena-trie` feature, the witness is rebuilt directly into
+/// [ArenaEthereumState](rsp_mpt::ArenaEthereumState), whose tries store their nodes in a single
+/// arena instead of allocating a `Box` per node.
+#[cfg(not(feature = "arena-trie"))]
+pub type ClientState = EthereumState;
+
+/// The state the client programs rebuild from the [NodeStore] in which they read the witness.
+///
+/// With the `arena-trie` feature, the witness is rebuilt directly into
+/// [ArenaEthereumState](rsp_mpt::ArenaEthereumState), whose tries store their nodes in a single
+/// arena instead of allocating a `Box` per node.
+#[cfg(feature = "arena-trie")]
+pub type ClientState = rsp_mpt::ArenaEthereumState;
 
 /// The input for the client to execute a block and fully verify the STF (state transition
 /// function).
@@ -27,7 +47,7 @@ pub type OpClientExecutorInput = ClientExecutorInput<reth_optimism_primitives::O
 /// for the storage slots that were modified and accessed.
 #[serde_as]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-pub struct ClientExecutorInput<P: NodePrimitives> {
+pub struct ClientExecutorInput<P: NodePrimitives, S = EthereumState> {
     /// The current block (which will be executed inside the client).
     #[serde_as(
         as = "reth_primitives_traits::serde_bincode_compat::Block<'_, P::SignedTx, Header>"
@@ -38,18 +58,18 @@ pub struct ClientExecutorInput<P: NodePrimitives> {
     #[serde_as(as = "Vec<alloy_consensus::serde_bincode_compat::Header>")]
     pub ancestor_headers: Vec<Header>,
     /// Network state as of the parent block.
-    pub parent_state: EthereumState,
+    pub parent_state: S,
     /// Requests to account state and storage slots.
     pub state_requests: HashMap<Address, Vec<U256>>,
     /// Account bytecodes.
     pub bytecodes: Vec<Bytecode>,
     /// The genesis block, as a json string.
     pub genesis: Genesis,
-    /// The genesis block, as a json string.
+    /// The expected block beneficiary, checked against the signer of the block on Clique chains.
     pub custom_beneficiary: Option<Address>,
 }
 
-impl<P: NodePrimitives> ClientExecutorInput<P> {
+impl<P: NodePrimitives, S: StateCommitment> ClientExecutorInput<P, S> {
     /// Gets the immediate parent block's header.
     #[inline(always)]
     pub fn parent_header(&self) -> &Header {
@@ -57,14 +77,31 @@ impl<P: NodePrimitives> ClientExecutorInput<P> {
     }
 
     /// Creates a [`WitnessDb`].
-    pub fn witness_db(&self) -> Result<TrieDB<'_>, ClientError> {
+    pub fn witness_db(&self) -> Result<TrieDB<'_, S>, ClientError> {
         <Self as WitnessInput<P>>::witness_db(self)
     }
 }
 
-impl<P: NodePrimitives> WitnessInput<P> for ClientExecutorInput<P> {
+impl<P: NodePrimitives, S> ClientExecutorInput<P, S> {
+    /// Converts the witness with `f`, e.g. to or from the [NodeStore] the client programs read.
+    pub fn map_state<T>(self, f: impl FnOnce(S) -> T) -> ClientExecutorInput<P, T> {
+        ClientExecutorInput {
+            current_block: self.current_block,
+            ancestor_headers: self.ancestor_headers,
+            parent_state: f(self.parent_state),
+            state_requests: self.state_requests,
+            bytecodes: self.bytecodes,
+            genesis: self.genesis,
+            custom_beneficiary: self.custom_beneficiary,
+        }
+    }
+}
+
+impl<P: NodePrimitives, S: StateCommitment> WitnessInput<P> for ClientExecutorInput<P, S> {
+    type State = S;
+
     #[inline(always)]
-    fn state(&self) -> &EthereumState {
+    fn state(&self) -> &S {
         &self.parent_state
     }
 
@@ -89,16 +126,102 @@ impl<P: NodePrimitives> WitnessInput<P> for ClientExecutorInput<P> {
     }
 }
 
+/// The input for the client to execute a range of consecutive blocks.
+///
+/// The blocks share a single witness of the state as of the parent of the first block, which
+/// covers the state touched by all of them.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ClientExecutorRangeInput<P: NodePrimitives, S = EthereumState> {
+    /// The blocks to execute, in ascending order.
+    #[serde_as(
+        as = "Vec<reth_primitives_traits::serde_bincode_compat::Block<'_, P::SignedTx, Header>>"
+    )]
+    pub blocks: Vec<Block<P::SignedTx>>,
+    /// The block headers before the first block, starting from the most recent. There must be at
+    /// least one header to provide the parent state root.
+    #[serde_as(as = "Vec<alloy_consensus::serde_bincode_compat::Header>")]
+    pub ancestor_headers: Vec<Header>,
+    /// Network state as of the parent of the first block.
+    pub parent_state: S,
+    /// Requests to account state and storage slots.
+    pub state_requests: HashMap<Address, Vec<U256>>,
+    /// Account bytecodes.
+    pub bytecodes: Vec<Bytecode>,
+    /// The genesis block, as a json string.
+    pub genesis: Genesis,
+    /// The expected beneficiary of every block, checked against the signers of the blocks on
+    /// Clique chains.
+    pub custom_beneficiary: Option<Address>,
+}
+
+impl<P: NodePrimitives, S: StateCommitment> ClientExecutorRangeInput<P, S> {
+    /// Gets the header of the parent of the first block.
+    #[inline(always)]
+    pub fn parent_header(&self) -> &Header {
+        &self.ancestor_headers[0]
+    }
+
+    /// Creates a [`WitnessDb`].
+    pub fn witness_db(&self) -> Result<TrieDB<'_, S>, ClientError> {
+        <Self as WitnessInput<P>>::witness_db(self)
+    }
+}
+
+impl<P: NodePrimitives, S> ClientExecutorRangeInput<P, S> {
+    /// Converts the witness with `f`, e.g. to or from the [NodeStore] the client programs read.
+    pub fn map_state<T>(self, f: impl FnOnce(S) -> T) -> ClientExecutorRangeInput<P, T> {
+        ClientExecutorRangeInput {
+            blocks: self.blocks,
+            ancestor_headers: self.ancestor_headers,
+            parent_state: f(self.parent_state),
+            state_requests: self.state_requests,
+            bytecodes: self.bytecodes,
+            genesis: self.genesis,
+            custom_beneficiary: self.custom_beneficiary,
+        }
+    }
+}
+
+impl<P: NodePrimitives, S: StateCommitment> WitnessInput<P> for ClientExecutorRangeInput<P, S> {
+    type State = S;
+
+    #[inline(always)]
+    fn state(&self) -> &S {
+        &self.parent_state
+    }
+
+    #[inline(always)]
+    fn state_anchor(&self) -> B256 {
+        self.parent_header().state_root()
+    }
+
+    #[inline(always)]
+    fn state_requests(&self) -> impl Iterator<Item = (&Address, &Vec<U256>)> {
+        self.state_requests.iter()
+    }
+
+    #[inline(always)]
+    fn bytecodes(&self) -> impl Iterator<Item = &Bytecode> {
+        self.bytecodes.iter()
+    }
+
+    #[inline(always)]
+    fn headers(&self) -> impl Iterator<Item = &Header> {
+        self.blocks.iter().rev().map(|block| &block.header).chain(self.ancestor_headers.iter())
+    }
+}
+
 #[derive(Debug)]
-pub struct TrieDB<'a> {
-    inner: &'a EthereumState,
+pub struct TrieDB<'a, S = EthereumState> {
+    inner: &'a S,
     block_hashes: HashMap<u64, B256>,
     bytecode_by_hash: HashMap<B256, &'a Bytecode>,
 }
 
-impl<'a> TrieDB<'a> {
+impl<'a, S> TrieDB<'a, S> {
     pub fn new(
-        inner: &'a EthereumState,
+        inner: &'a S,
         block_hashes: HashMap<u64, B256>,
         bytecode_by_hash: HashMap<B256, &'a Bytecode>,
     ) -> Self {
@@ -106,16 +229,13 @@ impl<'a> TrieDB<'a> {
     }
 }
 
-impl<'a> DatabaseRef for TrieDB<'a> {
+impl<'a, S: StateCommitment> DatabaseRef for TrieDB<'a, S> {
     /// The database error type.
     type Error = ProviderError;
 
     /// Get basic account information.
     fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
-        let hashed_address = keccak256(address);
-        let hashed_address = hashed_address.as_slice();
-
-        let account_in_trie = self.inner.state_trie.get_rlp::<TrieAccount>(hashed_address).unwrap();
+        let account_in_trie = self.inner.account(address).unwrap();
 
         let account = account_in_trie.map(|account_in_trie| AccountInfo {
             balance: account_in_trie.balance,
@@ -134,19 +254,9 @@ impl<'a> DatabaseRef for TrieDB<'a> {
 
     /// Get storage value of address at index.
     fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
-        let hashed_address = keccak256(address);
-        let hashed_address = hashed_address.as_slice();
-
-        let storage_trie = self
-            .inner
-            .storage_tries
-            .get(hashed_address)
-            .expect("A storage trie must be provided for each account");
-
-        Ok(storage_trie
-            .get_rlp::<U256>(keccak256(index.to_be_bytes::<32>()).as_slice())
-            .expect("Can get from MPT")
-            .unwrap_or_default())
+        self.inner
+            .storage(address, index)
+            .map_err(|e| ProviderError::Database(DatabaseError::Other(e.to_string())))
     }
 
     /// Get block hash by block number.
@@ -160,8 +270,11 @@ impl<'a> DatabaseRef for TrieDB<'a> {
 
 /// A trait for constructing [`WitnessDb`].
 pub trait WitnessInput<P: NodePrimitives> {
+    /// The commitment to the state from which account info and storage slots are loaded.
+    type State: StateCommitment;
+
     /// Gets a reference to the state from which account info and storage slots are loaded.
-    fn state(&self) -> &EthereumState;
+    fn state(&self) -> &Self::State;
 
     /// Gets the state trie root hash that the state referenced by
     /// [state()](trait.WitnessInput#tymethod.state) must conform to.
@@ -187,7 +300,7 @@ pub trait WitnessInput<P: NodePrimitives> {
     /// implementing this trait causes a zkVM run to cost over 5M cycles more. To avoid this, define
     /// a method inside the type that calls this trait method instead.
     #[inline(always)]
-    fn witness_db(&self) -> Result<TrieDB<'_>, ClientError> {
+    fn witness_db(&self) -> Result<TrieDB<'_, Self::State>, ClientError> {
         let state = self.state();
 
         if self.state_anchor() != state.state_root() {
@@ -2,9 +2,12 @@
 pub mod io;
 #[macro_use]
 mod utils;
+pub mod clique;
 pub mod custom;
 pub mod error;
 pub mod executor;
+pub mod optimism;
+pub mod public_values;
 
 mod into_primitives;
 pub use into_primitives::{FromInput, IntoInput, IntoPrimitives};
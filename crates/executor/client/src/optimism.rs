@@ -0,0 +1,374 @@
+//! OP Stack specific rules.
+
+use alloy_consensus::Transaction;
+use alloy_primitives::{address, keccak256, Address, B256, U256};
+use reth_trie::EMPTY_ROOT_HASH;
+use rsp_mpt::{Error as MptError, StateCommitment};
+use serde::{Deserialize, Serialize};
+
+/// The address of the `L2ToL1MessagePasser` predeploy, which stores the withdrawals initiated on
+/// L2.
+pub const L2_TO_L1_MESSAGE_PASSER_ADDRESS: Address =
+    address!("4200000000000000000000000000000000000016");
+
+/// The address of the `L1Block` predeploy, which holds the attributes of the L1 origin of the
+/// L2 blocks.
+pub const L1_BLOCK_ADDRESS: Address = address!("4200000000000000000000000000000000000015");
+
+/// The address of the `OperatorFeeVault` predeploy, which receives the operator fees from
+/// Isthmus on.
+pub const OPERATOR_FEE_VAULT_ADDRESS: Address =
+    address!("420000000000000000000000000000000000001b");
+
+/// The slot of the `L1Block` predeploy packing the operator fee scalar and constant.
+const OPERATOR_FEE_SLOT: U256 = U256::from_limbs([8, 0, 0, 0]);
+
+/// The operator fee scalar is expressed in millionths.
+const OPERATOR_FEE_SCALAR_DECIMALS: u64 = 1_000_000;
+
+/// The type of the OP Stack deposit transactions.
+const DEPOSIT_TX_TYPE: u8 = 0x7e;
+
+/// The selector of `setL1BlockValues`, whose ABI encoded arguments are the L1 attributes
+/// before Ecotone.
+const L1_INFO_BEDROCK_SELECTOR: [u8; 4] = [0x01, 0x5d, 0x8e, 0xb9];
+
+/// The selector of `setL1BlockValuesEcotone`, followed by the packed L1 attributes.
+const L1_INFO_ECOTONE_SELECTOR: [u8; 4] = [0x44, 0x0a, 0x5e, 0x20];
+
+/// The selector of `setL1BlockValuesIsthmus`, followed by the packed L1 attributes and the
+/// operator fee parameters.
+const L1_INFO_ISTHMUS_SELECTOR: [u8; 4] = [0x09, 0x89, 0x99, 0xbe];
+
+/// Returns the storage root of the `L2ToL1MessagePasser`, which is the withdrawals root of the
+/// OP Stack blocks from Isthmus on.
+///
+/// The account of the `L2ToL1MessagePasser` must be part of the state.
+pub fn message_passer_storage_root<S: StateCommitment>(state: &S) -> Result<B256, MptError> {
+    Ok(state
+        .account(L2_TO_L1_MESSAGE_PASSER_ADDRESS)?
+        .map_or(EMPTY_ROOT_HASH, |account| account.storage_root))
+}
+
+/// The parameters of the operator fee charged to the non-deposit transactions from Isthmus on,
+/// set in the `L1Block` predeploy by the L1 attributes deposit transaction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OperatorFee {
+    /// The fee per gas, in millionths of wei.
+    pub scalar: u32,
+    /// The fee per transaction, in wei.
+    pub constant: u64,
+}
+
+impl OperatorFee {
+    /// Decodes the operator fee parameters from the `L1Block` storage slot packing them: the
+    /// scalar is stored in the bytes 20 to 24 of the big-endian slot, the constant in the last 8
+    /// bytes.
+    pub fn from_slot(slot: U256) -> Self {
+        let bytes = slot.to_be_bytes::<32>();
+        Self {
+            scalar: u32::from_be_bytes(bytes[20..24].try_into().unwrap()),
+            constant: u64::from_be_bytes(bytes[24..].try_into().unwrap()),
+        }
+    }
+
+    /// Returns the operator fee of a transaction using `gas`:
+    /// `gas * scalar / 1_000_000 + constant`.
+    pub fn charge(&self, gas: u64) -> U256 {
+        let fee = U256::from(gas).saturating_mul(U256::from(self.scalar));
+        fee / U256::from(OPERATOR_FEE_SCALAR_DECIMALS) + U256::from(self.constant)
+    }
+}
+
+#[cfg(feature = "optimism")]
+pub use operator_fee::operator_fee_handler_register;
+
+/// The EVM handlers charging the operator fee, which need the OP Stack transaction environment.
+#[cfg(feature = "optimism")]
+mod operator_fee {
+    use alloy_primitives::{Address, U256};
+    use revm::{
+        handler::register::EvmHandler,
+        primitives::{EVMError, InvalidTransaction},
+        Context, Database,
+    };
+    use std::sync::Arc;
+
+    use super::{OperatorFee, L1_BLOCK_ADDRESS, OPERATOR_FEE_SLOT, OPERATOR_FEE_VAULT_ADDRESS};
+
+    /// Charges the operator fee introduced by Isthmus, which is not implemented by the OP Stack
+    /// EVM of reth `v1.2.0`, to the non-deposit transactions.
+    ///
+    /// Like the gas, the fee for the gas limit is deducted from the caller before execution, the
+    /// fee for the unused gas is refunded after it, and the fee for the used gas is credited to
+    /// the `OperatorFeeVault`. The handlers of the OP Stack must be registered before this one.
+    pub fn operator_fee_handler_register<EXT, DB: Database>(handler: &mut EvmHandler<'_, EXT, DB>) {
+        let deduct_caller = handler.pre_execution.deduct_caller.clone();
+        handler.pre_execution.deduct_caller = Arc::new(move |context| {
+            deduct_caller(context)?;
+            if is_deposit(context) {
+                return Ok(());
+            }
+
+            let charge = operator_fee(context)?.charge(context.evm.inner.env.tx.gas_limit);
+            let caller = context.evm.inner.env.tx.caller;
+            let mut account = context
+                .evm
+                .inner
+                .journaled_state
+                .load_account(caller, &mut context.evm.inner.db)?;
+            if charge > account.data.info.balance {
+                return Err(EVMError::Transaction(InvalidTransaction::LackOfFundForMaxFee {
+                    fee: Box::new(charge),
+                    balance: Box::new(account.data.info.balance),
+                }));
+            }
+            account.data.info.balance -= charge;
+
+            Ok(())
+        });
+
+        let reimburse_caller = handler.post_execution.reimburse_caller.clone();
+        handler.post_execution.reimburse_caller = Arc::new(move |context, gas| {
+            reimburse_caller(context, gas)?;
+            if is_deposit(context) {
+                return Ok(());
+            }
+
+            let operator_fee = operator_fee(context)?;
+            let gas_used = gas.spent() - gas.refunded() as u64;
+            let refund =
+                operator_fee.charge(gas.limit()).saturating_sub(operator_fee.charge(gas_used));
+            let caller = context.evm.inner.env.tx.caller;
+            credit(context, caller, refund)
+        });
+
+        let reward_beneficiary = handler.post_execution.reward_beneficiary.clone();
+        handler.post_execution.reward_beneficiary = Arc::new(move |context, gas| {
+            reward_beneficiary(context, gas)?;
+            if is_deposit(context) {
+                return Ok(());
+            }
+
+            let fee = operator_fee(context)?.charge(gas.spent() - gas.refunded() as u64);
+            credit(context, OPERATOR_FEE_VAULT_ADDRESS, fee)
+        });
+    }
+
+    /// Returns whether the transaction being executed is a deposit transaction, which has a source
+    /// hash.
+    fn is_deposit<EXT, DB: Database>(context: &Context<EXT, DB>) -> bool {
+        context.evm.inner.env.tx.optimism.source_hash.is_some()
+    }
+
+    /// Reads the operator fee parameters of the block from the `L1Block` predeploy.
+    fn operator_fee<EXT, DB: Database>(
+        context: &mut Context<EXT, DB>,
+    ) -> Result<OperatorFee, EVMError<DB::Error>> {
+        let slot = context
+            .evm
+            .inner
+            .db
+            .storage(L1_BLOCK_ADDRESS, OPERATOR_FEE_SLOT)
+            .map_err(EVMError::Database)?;
+
+        Ok(OperatorFee::from_slot(slot))
+    }
+
+    /// Adds `amount` to the balance of the account at `address`.
+    fn credit<EXT, DB: Database>(
+        context: &mut Context<EXT, DB>,
+        address: Address,
+        amount: U256,
+    ) -> Result<(), EVMError<DB::Error>> {
+        let mut account =
+            context.evm.inner.journaled_state.load_account(address, &mut context.evm.inner.db)?;
+        account.data.mark_touch();
+        account.data.info.balance = account.data.info.balance.saturating_add(amount);
+
+        Ok(())
+    }
+}
+
+/// The version of the [OutputRoot] encoding.
+pub const OUTPUT_ROOT_VERSION: B256 = B256::ZERO;
+
+/// The commitment to an L2 block that OP Stack output proposals and fault proofs are made of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputRoot {
+    /// The state root of the block.
+    pub state_root: B256,
+    /// The storage root of the `L2ToL1MessagePasser` after the block.
+    pub message_passer_storage_root: B256,
+    /// The hash of the block.
+    pub block_hash: B256,
+}
+
+impl OutputRoot {
+    /// Returns the output root:
+    /// `keccak256(version || state_root || message_passer_storage_root || block_hash)`.
+    pub fn hash(&self) -> B256 {
+        let mut preimage = [0u8; 128];
+        preimage[..32].copy_from_slice(OUTPUT_ROOT_VERSION.as_slice());
+        preimage[32..64].copy_from_slice(self.state_root.as_slice());
+        preimage[64..96].copy_from_slice(self.message_passer_storage_root.as_slice());
+        preimage[96..].copy_from_slice(self.block_hash.as_slice());
+        keccak256(preimage)
+    }
+}
+
+/// The L1 block an OP Stack block is derived from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct L1Origin {
+    /// The number of the L1 block.
+    pub number: u64,
+    /// The hash of the L1 block.
+    pub hash: B256,
+}
+
+impl L1Origin {
+    /// Decodes the L1 origin from the calldata of the L1 attributes deposit transaction.
+    ///
+    /// All the versions of the calldata store the L1 block number in the last 8 bytes of their
+    /// first word and the L1 block hash in their fourth word.
+    pub fn from_l1_attributes(calldata: &[u8]) -> Option<Self> {
+        let selector: [u8; 4] = calldata.get(..4)?.try_into().ok()?;
+        let len = match selector {
+            L1_INFO_BEDROCK_SELECTOR => 260,
+            L1_INFO_ECOTONE_SELECTOR => 164,
+            L1_INFO_ISTHMUS_SELECTOR => 176,
+            _ => return None,
+        };
+        if calldata.len() != len {
+            return None;
+        }
+
+        Some(Self {
+            number: u64::from_be_bytes(calldata[28..36].try_into().unwrap()),
+            hash: B256::from_slice(&calldata[100..132]),
+        })
+    }
+}
+
+/// Returns the L1 origin of an OP Stack block, set by its first transaction, the L1 attributes
+/// deposit transaction to the `L1Block` predeploy.
+pub fn l1_origin<T: Transaction>(transactions: &[T]) -> Option<L1Origin> {
+    let tx = transactions.first()?;
+    if tx.ty() != DEPOSIT_TX_TYPE || tx.to() != Some(L1_BLOCK_ADDRESS) {
+        return None;
+    }
+
+    L1Origin::from_l1_attributes(tx.input())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_l1_origin_from_l1_attributes() {
+        let hash = B256::repeat_byte(0xab);
+
+        let mut bedrock = vec![0u8; 260];
+        bedrock[..4].copy_from_slice(&L1_INFO_BEDROCK_SELECTOR);
+        bedrock[28..36].copy_from_slice(&17_000_000u64.to_be_bytes());
+        bedrock[100..132].copy_from_slice(hash.as_slice());
+        assert_eq!(
+            L1Origin::from_l1_attributes(&bedrock),
+            Some(L1Origin { number: 17_000_000, hash })
+        );
+
+        // The packed encoding: the fee scalars, the sequence number and the timestamp precede
+        // the number, the base fees precede the hash.
+        let mut ecotone = vec![0u8; 164];
+        ecotone[..4].copy_from_slice(&L1_INFO_ECOTONE_SELECTOR);
+        ecotone[28..36].copy_from_slice(&20_000_000u64.to_be_bytes());
+        ecotone[100..132].copy_from_slice(hash.as_slice());
+        assert_eq!(
+            L1Origin::from_l1_attributes(&ecotone),
+            Some(L1Origin { number: 20_000_000, hash })
+        );
+
+        let mut isthmus = ecotone.clone();
+        isthmus[..4].copy_from_slice(&L1_INFO_ISTHMUS_SELECTOR);
+        isthmus.extend_from_slice(&[0u8; 12]);
+        assert_eq!(
+            L1Origin::from_l1_attributes(&isthmus),
+            Some(L1Origin { number: 20_000_000, hash })
+        );
+
+        // The length must match the version.
+        assert_eq!(L1Origin::from_l1_attributes(&ecotone[..163]), None);
+        ecotone[..4].copy_from_slice(&L1_INFO_ISTHMUS_SELECTOR);
+        assert_eq!(L1Origin::from_l1_attributes(&ecotone), None);
+        assert_eq!(L1Origin::from_l1_attributes(&[]), None);
+    }
+
+    #[test]
+    fn test_operator_fee_from_slot() {
+        let mut slot = [0u8; 32];
+        slot[20..24].copy_from_slice(&1_500_000u32.to_be_bytes());
+        slot[24..].copy_from_slice(&1_000u64.to_be_bytes());
+        let operator_fee = OperatorFee::from_slot(U256::from_be_bytes(slot));
+
+        assert_eq!(operator_fee, OperatorFee { scalar: 1_500_000, constant: 1_000 });
+        assert_eq!(operator_fee.charge(21_000), U256::from(21_000 * 3 / 2 + 1_000));
+        assert_eq!(OperatorFee::default().charge(21_000), U256::ZERO);
+    }
+
+    #[cfg(feature = "optimism")]
+    #[test]
+    fn test_operator_fee_handler_register() {
+        use alloy_primitives::TxKind;
+        use revm::{
+            db::{CacheDB, EmptyDB},
+            primitives::{AccountInfo, SpecId},
+            Evm,
+        };
+
+        let operator_fee = OperatorFee { scalar: 2_000_000, constant: 1_000 };
+        let mut slot = [0u8; 32];
+        slot[20..24].copy_from_slice(&operator_fee.scalar.to_be_bytes());
+        slot[24..].copy_from_slice(&operator_fee.constant.to_be_bytes());
+
+        let caller = Address::repeat_byte(0x01);
+        let balance = U256::from(1_000_000_000u64);
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(caller, AccountInfo { balance, ..Default::default() });
+        db.insert_account_storage(L1_BLOCK_ADDRESS, OPERATOR_FEE_SLOT, U256::from_be_bytes(slot))
+            .unwrap();
+
+        // A transfer using 21000 gas out of 100000, for 1 wei per gas.
+        let transact = |deposit: bool| {
+            let mut evm = Evm::builder()
+                .with_db(db.clone())
+                .optimism()
+                .with_spec_id(SpecId::GRANITE)
+                .modify_tx_env(|tx| {
+                    tx.caller = caller;
+                    tx.transact_to = TxKind::Call(Address::repeat_byte(0x02));
+                    tx.gas_limit = 100_000;
+                    tx.gas_price = U256::from(1);
+                    tx.optimism.enveloped_tx = Some(Default::default());
+                    if deposit {
+                        tx.optimism.source_hash = Some(B256::repeat_byte(0x03));
+                    }
+                })
+                .append_handler_register(operator_fee_handler_register)
+                .build();
+            evm.transact().unwrap()
+        };
+
+        let result = transact(false);
+        assert_eq!(result.result.gas_used(), 21_000);
+        let fee = operator_fee.charge(21_000);
+        assert_eq!(fee, U256::from(43_000));
+        assert_eq!(result.state[&OPERATOR_FEE_VAULT_ADDRESS].info.balance, fee);
+        assert_eq!(result.state[&caller].info.balance, balance - U256::from(21_000) - fee);
+
+        // Deposit transactions do not pay the operator fee.
+        let result = transact(true);
+        let vault = result.state.get(&OPERATOR_FEE_VAULT_ADDRESS);
+        assert_eq!(vault.map(|account| account.info.balance).unwrap_or_default(), U256::ZERO);
+    }
+}
@@ -0,0 +1,472 @@
+//! The public values committed by the client programs.
+
+use alloy_consensus::Header;
+use alloy_primitives::{keccak256, B256};
+use alloy_sol_types::{sol, SolValue};
+use serde::{Deserialize, Serialize};
+
+use crate::error::ClientError;
+
+/// The version of the [PublicValues] layout.
+pub const PUBLIC_VALUES_VERSION: u8 = 1;
+
+sol! {
+    /// The Solidity ABI encoding of [PublicValues].
+    struct PublicValuesStruct {
+        uint8 version;
+        uint64 chainId;
+        bytes32 chainConfigHash;
+        bytes32 parentHash;
+        bytes32 blockHash;
+        uint64 blockNumber;
+        bytes32 stateRoot;
+        uint64 gasUsed;
+        bytes32 inputHash;
+    }
+
+    /// The Solidity ABI encoding of [RangePublicValues].
+    struct RangePublicValuesStruct {
+        uint8 version;
+        uint64 chainId;
+        bytes32 chainConfigHash;
+        bytes32 parentHash;
+        bytes32 blockHash;
+        uint64 firstBlockNumber;
+        uint64 lastBlockNumber;
+        bytes32 stateRoot;
+        bytes32 inputHash;
+    }
+
+    /// The Solidity ABI encoding of [AggregationPublicValues].
+    struct AggregationPublicValuesStruct {
+        uint8 version;
+        bytes32 vkeyHash;
+        uint64 chainId;
+        bytes32 chainConfigHash;
+        bytes32 parentHash;
+        bytes32 blockHash;
+        uint64 firstBlockNumber;
+        uint64 lastBlockNumber;
+    }
+
+    /// The Solidity ABI encoding of [OpPublicValues].
+    struct OpPublicValuesStruct {
+        bytes32 outputRoot;
+        bytes32 l1OriginHash;
+        uint64 l1OriginNumber;
+    }
+}
+
+/// The public values committed by the client programs, which bind the executed block to the
+/// chain and to the input it was executed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicValues {
+    /// The version of the layout, [PUBLIC_VALUES_VERSION].
+    pub version: u8,
+    /// The id of the chain.
+    pub chain_id: u64,
+    /// The hash of the chain config, see
+    /// [chain_config_hash](rsp_primitives::genesis::chain_config_hash).
+    pub chain_config_hash: B256,
+    /// The hash of the parent block.
+    pub parent_hash: B256,
+    /// The hash of the block.
+    pub block_hash: B256,
+    /// The number of the block.
+    pub block_number: u64,
+    /// The state root after the block.
+    pub state_root: B256,
+    /// The gas used by the block.
+    pub gas_used: u64,
+    /// The commitment to the serialized client input, see [PublicValues::hash_input].
+    pub input_hash: B256,
+}
+
+impl PublicValues {
+    /// The size of the Solidity ABI encoding.
+    pub const ABI_ENCODED_SIZE: usize = 9 * 32;
+
+    /// Returns the public values of the executed block.
+    pub fn new(chain_id: u64, chain_config_hash: B256, input_hash: B256, header: &Header) -> Self {
+        Self {
+            version: PUBLIC_VALUES_VERSION,
+            chain_id,
+            chain_config_hash,
+            parent_hash: header.parent_hash,
+            block_hash: header.hash_slow(),
+            block_number: header.number,
+            state_root: header.state_root,
+            gas_used: header.gas_used,
+            input_hash,
+        }
+    }
+
+    /// Returns the commitment to the bincode serialized client input.
+    pub fn hash_input(serialized_input: &[u8]) -> B256 {
+        keccak256(serialized_input)
+    }
+
+    /// Returns the Solidity ABI encoding of the public values.
+    pub fn abi_encode(&self) -> Vec<u8> {
+        PublicValuesStruct::from(*self).abi_encode()
+    }
+
+    /// Decodes the public values from their Solidity ABI encoding.
+    pub fn abi_decode(data: &[u8]) -> Result<Self, alloy_sol_types::Error> {
+        PublicValuesStruct::abi_decode(data, true).map(Into::into)
+    }
+}
+
+impl From<PublicValues> for PublicValuesStruct {
+    fn from(value: PublicValues) -> Self {
+        Self {
+            version: value.version,
+            chainId: value.chain_id,
+            chainConfigHash: value.chain_config_hash,
+            parentHash: value.parent_hash,
+            blockHash: value.block_hash,
+            blockNumber: value.block_number,
+            stateRoot: value.state_root,
+            gasUsed: value.gas_used,
+            inputHash: value.input_hash,
+        }
+    }
+}
+
+impl From<PublicValuesStruct> for PublicValues {
+    fn from(value: PublicValuesStruct) -> Self {
+        Self {
+            version: value.version,
+            chain_id: value.chainId,
+            chain_config_hash: value.chainConfigHash,
+            parent_hash: value.parentHash,
+            block_hash: value.blockHash,
+            block_number: value.blockNumber,
+            state_root: value.stateRoot,
+            gas_used: value.gasUsed,
+            input_hash: value.inputHash,
+        }
+    }
+}
+
+/// The public values committed by the client program executing a range of blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RangePublicValues {
+    /// The version of the layout, [PUBLIC_VALUES_VERSION].
+    pub version: u8,
+    /// The id of the chain.
+    pub chain_id: u64,
+    /// The hash of the chain config, see
+    /// [chain_config_hash](rsp_primitives::genesis::chain_config_hash).
+    pub chain_config_hash: B256,
+    /// The hash of the parent of the first block.
+    pub parent_hash: B256,
+    /// The hash of the last block.
+    pub block_hash: B256,
+    /// The number of the first block.
+    pub first_block_number: u64,
+    /// The number of the last block.
+    pub last_block_number: u64,
+    /// The state root after the last block.
+    pub state_root: B256,
+    /// The commitment to the serialized client input, see [PublicValues::hash_input].
+    pub input_hash: B256,
+}
+
+impl RangePublicValues {
+    /// The size of the Solidity ABI encoding.
+    pub const ABI_ENCODED_SIZE: usize = 9 * 32;
+
+    /// Returns the public values of the executed blocks, whose `headers` must not be empty.
+    pub fn new(
+        chain_id: u64,
+        chain_config_hash: B256,
+        input_hash: B256,
+        headers: &[Header],
+    ) -> Self {
+        let (first, last) = (headers.first().unwrap(), headers.last().unwrap());
+        Self {
+            version: PUBLIC_VALUES_VERSION,
+            chain_id,
+            chain_config_hash,
+            parent_hash: first.parent_hash,
+            block_hash: last.hash_slow(),
+            first_block_number: first.number,
+            last_block_number: last.number,
+            state_root: last.state_root,
+            input_hash,
+        }
+    }
+
+    /// Returns the Solidity ABI encoding of the public values.
+    pub fn abi_encode(&self) -> Vec<u8> {
+        RangePublicValuesStruct {
+            version: self.version,
+            chainId: self.chain_id,
+            chainConfigHash: self.chain_config_hash,
+            parentHash: self.parent_hash,
+            blockHash: self.block_hash,
+            firstBlockNumber: self.first_block_number,
+            lastBlockNumber: self.last_block_number,
+            stateRoot: self.state_root,
+            inputHash: self.input_hash,
+        }
+        .abi_encode()
+    }
+
+    /// Decodes the public values from their Solidity ABI encoding.
+    pub fn abi_decode(data: &[u8]) -> Result<Self, alloy_sol_types::Error> {
+        RangePublicValuesStruct::abi_decode(data, true).map(|value| Self {
+            version: value.version,
+            chain_id: value.chainId,
+            chain_config_hash: value.chainConfigHash,
+            parent_hash: value.parentHash,
+            block_hash: value.blockHash,
+            first_block_number: value.firstBlockNumber,
+            last_block_number: value.lastBlockNumber,
+            state_root: value.stateRoot,
+            input_hash: value.inputHash,
+        })
+    }
+}
+
+/// The public values committed by the aggregation program, which verifies the proofs of
+/// consecutive blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AggregationPublicValues {
+    /// The version of the layout, [PUBLIC_VALUES_VERSION].
+    pub version: u8,
+    /// The hash of the verification key of the client program the block proofs are verified
+    /// against, see [AggregationPublicValues::vkey_hash].
+    pub vkey_hash: B256,
+    /// The id of the chain.
+    pub chain_id: u64,
+    /// The hash of the chain config, see
+    /// [chain_config_hash](rsp_primitives::genesis::chain_config_hash).
+    pub chain_config_hash: B256,
+    /// The hash of the parent of the first block.
+    pub parent_hash: B256,
+    /// The hash of the last block.
+    pub block_hash: B256,
+    /// The number of the first block.
+    pub first_block_number: u64,
+    /// The number of the last block.
+    pub last_block_number: u64,
+}
+
+impl AggregationPublicValues {
+    /// The size of the Solidity ABI encoding.
+    pub const ABI_ENCODED_SIZE: usize = 8 * 32;
+
+    /// Returns the public values of the aggregation of the `blocks`, checking that they are
+    /// consecutive blocks of the same chain.
+    pub fn new(vkey_hash: B256, blocks: &[PublicValues]) -> Result<Self, ClientError> {
+        let (Some(first), Some(last)) = (blocks.first(), blocks.last()) else {
+            return Err(ClientError::EmptyBlockRange);
+        };
+
+        for block in blocks {
+            if block.version != PUBLIC_VALUES_VERSION {
+                return Err(ClientError::UnsupportedPublicValuesVersion(block.version));
+            }
+            if (block.chain_id, block.chain_config_hash)
+                != (first.chain_id, first.chain_config_hash)
+            {
+                return Err(ClientError::MismatchedChain(block.block_number));
+            }
+        }
+
+        for (parent, block) in blocks.iter().zip(blocks.iter().skip(1)) {
+            if parent.block_number + 1 != block.block_number {
+                return Err(ClientError::InvalidHeaderBlockNumber(
+                    parent.block_number + 1,
+                    block.block_number,
+                ));
+            }
+            if parent.block_hash != block.parent_hash {
+                return Err(ClientError::InvalidHeaderParentHash(
+                    parent.block_hash,
+                    block.parent_hash,
+                ));
+            }
+        }
+
+        Ok(Self {
+            version: PUBLIC_VALUES_VERSION,
+            vkey_hash,
+            chain_id: first.chain_id,
+            chain_config_hash: first.chain_config_hash,
+            parent_hash: first.parent_hash,
+            block_hash: last.block_hash,
+            first_block_number: first.block_number,
+            last_block_number: last.block_number,
+        })
+    }
+
+    /// Returns the hash of an SP1 verification key from its `hash_u32` digest, as big-endian
+    /// bytes.
+    pub fn vkey_hash(vkey: &[u32; 8]) -> B256 {
+        let mut hash = B256::ZERO;
+        for (chunk, word) in hash.chunks_exact_mut(4).zip(vkey) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        hash
+    }
+
+    /// Returns the Solidity ABI encoding of the public values.
+    pub fn abi_encode(&self) -> Vec<u8> {
+        AggregationPublicValuesStruct {
+            version: self.version,
+            vkeyHash: self.vkey_hash,
+            chainId: self.chain_id,
+            chainConfigHash: self.chain_config_hash,
+            parentHash: self.parent_hash,
+            blockHash: self.block_hash,
+            firstBlockNumber: self.first_block_number,
+            lastBlockNumber: self.last_block_number,
+        }
+        .abi_encode()
+    }
+
+    /// Decodes the public values from their Solidity ABI encoding.
+    pub fn abi_decode(data: &[u8]) -> Result<Self, alloy_sol_types::Error> {
+        AggregationPublicValuesStruct::abi_decode(data, true).map(|value| Self {
+            version: value.version,
+            vkey_hash: value.vkeyHash,
+            chain_id: value.chainId,
+            chain_config_hash: value.chainConfigHash,
+            parent_hash: value.parentHash,
+            block_hash: value.blockHash,
+            first_block_number: value.firstBlockNumber,
+            last_block_number: value.lastBlockNumber,
+        })
+    }
+}
+
+/// The public values committed by the OP Stack client program after the [PublicValues].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpPublicValues {
+    /// The output root of the block, which can be submitted as an output proposal.
+    pub output_root: B256,
+    /// The hash of the L1 block the block is derived from.
+    pub l1_origin_hash: B256,
+    /// The number of the L1 block the block is derived from.
+    pub l1_origin_number: u64,
+}
+
+impl OpPublicValues {
+    /// The size of the Solidity ABI encoding.
+    pub const ABI_ENCODED_SIZE: usize = 3 * 32;
+
+    /// Returns the Solidity ABI encoding of the public values.
+    pub fn abi_encode(&self) -> Vec<u8> {
+        OpPublicValuesStruct {
+            outputRoot: self.output_root,
+            l1OriginHash: self.l1_origin_hash,
+            l1OriginNumber: self.l1_origin_number,
+        }
+        .abi_encode()
+    }
+
+    /// Decodes the public values from their Solidity ABI encoding.
+    pub fn abi_decode(data: &[u8]) -> Result<Self, alloy_sol_types::Error> {
+        OpPublicValuesStruct::abi_decode(data, true).map(|value| Self {
+            output_root: value.outputRoot,
+            l1_origin_hash: value.l1OriginHash,
+            l1_origin_number: value.l1OriginNumber,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_values_encoding() {
+        let header = Header { number: 42, gas_used: 21_000, ..Default::default() };
+        let public_values = PublicValues::new(
+            1,
+            B256::repeat_byte(0x01),
+            PublicValues::hash_input(b"input"),
+            &header,
+        );
+        assert_eq!(public_values.block_hash, header.hash_slow());
+
+        let encoded = public_values.abi_encode();
+        assert_eq!(encoded.len(), PublicValues::ABI_ENCODED_SIZE);
+        assert_eq!(encoded[31], PUBLIC_VALUES_VERSION);
+        assert_eq!(PublicValues::abi_decode(&encoded).unwrap(), public_values);
+
+        let op_public_values = OpPublicValues {
+            output_root: B256::repeat_byte(0x02),
+            l1_origin_hash: B256::repeat_byte(0x03),
+            l1_origin_number: 20_000_000,
+        };
+        let encoded = op_public_values.abi_encode();
+        assert_eq!(encoded.len(), OpPublicValues::ABI_ENCODED_SIZE);
+        assert_eq!(OpPublicValues::abi_decode(&encoded).unwrap(), op_public_values);
+
+        let next = Header { parent_hash: header.hash_slow(), number: 43, ..Default::default() };
+        let range_public_values = RangePublicValues::new(
+            1,
+            B256::repeat_byte(0x01),
+            B256::ZERO,
+            &[header.clone(), next.clone()],
+        );
+        assert_eq!(range_public_values.parent_hash, header.parent_hash);
+        assert_eq!(range_public_values.block_hash, next.hash_slow());
+        assert_eq!(
+            (range_public_values.first_block_number, range_public_values.last_block_number),
+            (42, 43)
+        );
+        let encoded = range_public_values.abi_encode();
+        assert_eq!(encoded.len(), RangePublicValues::ABI_ENCODED_SIZE);
+        assert_eq!(RangePublicValues::abi_decode(&encoded).unwrap(), range_public_values);
+    }
+
+    #[test]
+    fn test_aggregation_public_values() {
+        let mut parent_hash = B256::repeat_byte(0x01);
+        let blocks = (42..45)
+            .map(|number| {
+                let header = Header { parent_hash, number, ..Default::default() };
+                parent_hash = header.hash_slow();
+                PublicValues::new(1, B256::repeat_byte(0x02), B256::ZERO, &header)
+            })
+            .collect::<Vec<_>>();
+
+        let vkey_hash = AggregationPublicValues::vkey_hash(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(vkey_hash[..8], [0, 0, 0, 1, 0, 0, 0, 2]);
+
+        let aggregation = AggregationPublicValues::new(vkey_hash, &blocks).unwrap();
+        assert_eq!(aggregation.parent_hash, B256::repeat_byte(0x01));
+        assert_eq!(aggregation.block_hash, blocks[2].block_hash);
+        assert_eq!((aggregation.first_block_number, aggregation.last_block_number), (42, 44));
+        let encoded = aggregation.abi_encode();
+        assert_eq!(encoded.len(), AggregationPublicValues::ABI_ENCODED_SIZE);
+        assert_eq!(AggregationPublicValues::abi_decode(&encoded).unwrap(), aggregation);
+
+        // The blocks must be consecutive blocks of the same chain.
+        assert!(matches!(
+            AggregationPublicValues::new(vkey_hash, &[blocks[0], blocks[2]]),
+            Err(ClientError::InvalidHeaderBlockNumber(43, 44))
+        ));
+        let mut forked = blocks.clone();
+        forked[2].parent_hash = B256::ZERO;
+        assert!(matches!(
+            AggregationPublicValues::new(vkey_hash, &forked),
+            Err(ClientError::InvalidHeaderParentHash(_, _))
+        ));
+        let mut other_chain = blocks.clone();
+        other_chain[1].chain_id = 10;
+        assert!(matches!(
+            AggregationPublicValues::new(vkey_hash, &other_chain),
+            Err(ClientError::MismatchedChain(43))
+        ));
+        assert!(matches!(
+            AggregationPublicValues::new(vkey_hash, &[]),
+            Err(ClientError::EmptyBlockRange)
+        ));
+    }
+}
@@ -14,3 +14,21 @@ macro_rules! profile {
         }
     }};
 }
+
+/// Like [profile], but the cycles are also collected in the execution report under `$name`.
+macro_rules! profile_report {
+    ($name:expr, $block:block) => {{
+        #[cfg(target_os = "zkvm")]
+        {
+            println!("cycle-tracker-report-start: {}", $name);
+            let result = (|| $block)();
+            println!("cycle-tracker-report-end: {}", $name);
+            result
+        }
+
+        #[cfg(not(target_os = "zkvm"))]
+        {
+            $block
+        }
+    }};
+}
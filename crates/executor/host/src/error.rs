@@ -1,8 +1,11 @@
 use alloy_rpc_types::ConversionError;
 use alloy_transport::TransportError;
 use reth_errors::BlockExecutionError;
-use revm_primitives::B256;
-use rsp_mpt::FromProofError;
+use revm_primitives::{Address, B256};
+use rsp_client_executor::public_values::{
+    AggregationPublicValues, PublicValues, RangePublicValues,
+};
+use rsp_mpt::{Error as MptError, FromProofError, StateUpdateError};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -18,12 +21,32 @@ pub enum Error {
     ExecutionFailed(#[from] BlockExecutionError),
     #[error("Failed to construct a valid state trie from RPC data {}", .0)]
     FromProof(#[from] FromProofError),
+    #[error("Failed to update the state trie from RPC data {}", .0)]
+    StateUpdate(#[from] StateUpdateError),
+    #[error("Failed to read the state trie {}", .0)]
+    Mpt(#[from] MptError),
     #[error("RPC didnt have expected block height {}", .0)]
     ExpectedBlock(u64),
+    #[error("Invalid block range {}..={}", .0, .1)]
+    InvalidBlockRange(u64, u64),
     #[error("Header Mismatch \n found {} expected {}", .0, .1)]
     HeaderMismatch(B256, B256),
     #[error("State root mismatch after local execution \n found {} expected {}", .0, .1)]
     StateRootMismatch(B256, B256),
+    #[error("Invalid Clique signature in the header extra data")]
+    InvalidCliqueSeal,
+    #[error("Beneficiary mismatch \n supplied {} block {}", .0, .1)]
+    BeneficiaryMismatch(Address, Address),
+    #[error("Invalid public values length {}", .0)]
+    InvalidPublicValuesLength(usize),
+    #[error("Failed to decode the public values: {}", .0)]
+    PublicValuesDecoding(#[from] alloy_sol_types::Error),
+    #[error("Public values mismatch \n found {:?} expected {:?}", .0, .1)]
+    PublicValuesMismatch(Box<PublicValues>, Box<PublicValues>),
+    #[error("Range public values mismatch \n found {:?} expected {:?}", .0, .1)]
+    RangePublicValuesMismatch(Box<RangePublicValues>, Box<RangePublicValues>),
+    #[error("Aggregation public values mismatch \n found {:?} expected {:?}", .0, .1)]
+    AggregationPublicValuesMismatch(Box<AggregationPublicValues>, Box<AggregationPublicValues>),
     #[error("Failed to read the genesis file: {}", .0)]
     FailedToReadGenesisFile(#[from] std::io::Error),
 }
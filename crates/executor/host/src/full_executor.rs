@@ -4,18 +4,24 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use alloy_provider::{Network, Provider};
+use alloy_provider::{network::Ethereum, Network, Provider};
 use either::Either;
 use reth_evm::execute::BlockExecutionStrategyFactory;
 use reth_primitives::NodePrimitives;
-use revm_primitives::B256;
-use rsp_client_executor::{io::ClientExecutorInput, IntoInput, IntoPrimitives};
+use rsp_client_executor::{
+    io::{ClientExecutorInput, NodeStore},
+    public_values::{AggregationPublicValues, PublicValues, RangePublicValues},
+    IntoInput, IntoPrimitives,
+};
 use rsp_rpc_db::RpcDb;
 use serde::de::DeserializeOwned;
-use sp1_sdk::{EnvProver, SP1ProvingKey, SP1Stdin, SP1VerifyingKey};
+use sp1_sdk::{EnvProver, HashableKey, SP1Proof, SP1ProvingKey, SP1Stdin, SP1VerifyingKey};
 use tracing::warn;
 
-use crate::{Config, ExecutionHooks, HostExecutor};
+use crate::{
+    check_public_values, create_eth_host_executor, Config, EthHostExecutor, ExecutionHooks,
+    HostError, HostExecutor, PublicValuesDecoder,
+};
 
 pub type EitherExecutor<P, N, NP, F, H> =
     Either<FullExecutor<P, N, NP, F, H>, CachedExecutor<NP, H>>;
@@ -23,25 +29,19 @@ pub type EitherExecutor<P, N, NP, F, H> =
 pub fn build_executor<P, N, NP, F, H>(
     elf: Vec<u8>,
     provider: Option<P>,
-    block_execution_strategy_factory: F,
+    host_executor: HostExecutor<F>,
     hooks: H,
     config: Config,
 ) -> eyre::Result<EitherExecutor<P, N, NP, F, H>>
 where
     P: Provider<N> + Clone,
     N: Network,
-    NP: NodePrimitives + DeserializeOwned + IntoPrimitives<N> + IntoInput,
+    NP: NodePrimitives + DeserializeOwned + IntoPrimitives<N> + IntoInput + PublicValuesDecoder,
     F: BlockExecutionStrategyFactory<Primitives = NP>,
     H: ExecutionHooks,
 {
     if let Some(provider) = provider {
-        return Ok(Either::Left(FullExecutor::new(
-            provider,
-            elf,
-            block_execution_strategy_factory,
-            hooks,
-            config,
-        )));
+        return Ok(Either::Left(FullExecutor::new(provider, elf, host_executor, hooks, config)));
     }
 
     if let Some(cache_dir) = config.cache_dir {
@@ -66,7 +66,7 @@ impl<P, N, NP, F, H> BlockExecutor for EitherExecutor<P, N, NP, F, H>
 where
     P: Provider<N> + Clone,
     N: Network,
-    NP: NodePrimitives + DeserializeOwned + IntoPrimitives<N> + IntoInput,
+    NP: NodePrimitives + DeserializeOwned + IntoPrimitives<N> + IntoInput + PublicValuesDecoder,
     F: BlockExecutionStrategyFactory<Primitives = NP>,
     H: ExecutionHooks,
 {
@@ -82,7 +82,7 @@ pub struct FullExecutor<P, N, NP, F, H>
 where
     P: Provider<N> + Clone,
     N: Network,
-    NP: NodePrimitives + DeserializeOwned + IntoPrimitives<N> + IntoInput,
+    NP: NodePrimitives + DeserializeOwned + IntoPrimitives<N> + IntoInput + PublicValuesDecoder,
     F: BlockExecutionStrategyFactory<Primitives = NP>,
     H: ExecutionHooks,
 {
@@ -100,14 +100,14 @@ impl<P, N, NP, F, H> FullExecutor<P, N, NP, F, H>
 where
     P: Provider<N> + Clone,
     N: Network,
-    NP: NodePrimitives + DeserializeOwned + IntoPrimitives<N> + IntoInput,
+    NP: NodePrimitives + DeserializeOwned + IntoPrimitives<N> + IntoInput + PublicValuesDecoder,
     F: BlockExecutionStrategyFactory<Primitives = NP>,
     H: ExecutionHooks,
 {
     pub fn new(
         provider: P,
         elf: Vec<u8>,
-        block_execution_strategy_factory: F,
+        host_executor: HostExecutor<F>,
         hooks: H,
         config: Config,
     ) -> Self {
@@ -116,16 +116,7 @@ where
         // Setup the proving key and verification key.
         let (pk, vk) = client.setup(&elf);
 
-        Self {
-            provider,
-            host_executor: HostExecutor::new(block_execution_strategy_factory),
-            client,
-            pk,
-            vk,
-            hooks,
-            config,
-            phantom: Default::default(),
-        }
+        Self { provider, host_executor, client, pk, vk, hooks, config, phantom: Default::default() }
     }
 }
 
@@ -133,7 +124,7 @@ impl<P, N, NP, F, H> BlockExecutor for FullExecutor<P, N, NP, F, H>
 where
     P: Provider<N> + Clone,
     N: Network,
-    NP: NodePrimitives + DeserializeOwned + IntoPrimitives<N> + IntoInput,
+    NP: NodePrimitives + DeserializeOwned + IntoPrimitives<N> + IntoInput + PublicValuesDecoder,
     F: BlockExecutionStrategyFactory<Primitives = NP>,
     H: ExecutionHooks,
 {
@@ -166,6 +157,7 @@ where
                         self.config.custom_beneficiary,
                     )
                     .await?;
+                tracing::info!("witness composition:\n{}", client_input.parent_state.stats());
 
                 if let Some(ref cache_dir) = self.config.cache_dir {
                     let input_folder = cache_dir.join(format!("input/{}", self.config.chain.id()));
@@ -185,6 +177,7 @@ where
 
         execute_client(
             client_input,
+            self.config.chain.id(),
             &self.client,
             &self.pk,
             &self.vk,
@@ -201,7 +194,7 @@ impl<P, N, NP, F, H> Debug for FullExecutor<P, N, NP, F, H>
 where
     P: Provider<N> + Clone,
     N: Network,
-    NP: NodePrimitives + DeserializeOwned + IntoPrimitives<N> + IntoInput,
+    NP: NodePrimitives + DeserializeOwned + IntoPrimitives<N> + IntoInput + PublicValuesDecoder,
     F: BlockExecutionStrategyFactory<Primitives = NP>,
     H: ExecutionHooks,
 {
@@ -223,7 +216,7 @@ pub struct CachedExecutor<NP: NodePrimitives, H: ExecutionHooks> {
 
 impl<NP, H> CachedExecutor<NP, H>
 where
-    NP: NodePrimitives + DeserializeOwned,
+    NP: NodePrimitives + DeserializeOwned + PublicValuesDecoder,
     H: ExecutionHooks,
 {
     pub fn new(elf: Vec<u8>, hooks: H, cache_dir: PathBuf, chain_id: u64, prove: bool) -> Self {
@@ -238,7 +231,7 @@ where
 
 impl<NP, H> BlockExecutor for CachedExecutor<NP, H>
 where
-    NP: NodePrimitives + DeserializeOwned,
+    NP: NodePrimitives + DeserializeOwned + PublicValuesDecoder,
     H: ExecutionHooks,
 {
     async fn execute(&mut self, block_number: u64) -> eyre::Result<()> {
@@ -246,8 +239,16 @@ where
             try_load_input_from_cache::<NP>(&self.cache_dir, self.chain_id, block_number)?
                 .ok_or(eyre::eyre!("No cached input found"))?;
 
-        execute_client(client_input, &self.client, &self.pk, &self.vk, &mut self.hooks, self.prove)
-            .await
+        execute_client(
+            client_input,
+            self.chain_id,
+            &self.client,
+            &self.pk,
+            &self.vk,
+            &mut self.hooks,
+            self.prove,
+        )
+        .await
     }
 }
 
@@ -257,8 +258,9 @@ impl<NP: NodePrimitives, H: ExecutionHooks> Debug for CachedExecutor<NP, H> {
     }
 }
 
-async fn execute_client<P: NodePrimitives, H: ExecutionHooks>(
+async fn execute_client<P: PublicValuesDecoder, H: ExecutionHooks>(
     client_input: ClientExecutorInput<P>,
+    chain_id: u64,
     client: &EnvProver,
     pk: &SP1ProvingKey,
     vk: &SP1VerifyingKey,
@@ -268,16 +270,26 @@ async fn execute_client<P: NodePrimitives, H: ExecutionHooks>(
     // Generate the proof.
     // Execute the block inside the zkVM.
     let mut stdin = SP1Stdin::new();
-    let buffer = bincode::serialize(&client_input).unwrap();
+    // The client programs read the witness as a flat list of nodes.
+    let program_input = client_input.clone().map_state(|state| NodeStore::from(&state));
+    let buffer = bincode::serialize(&program_input).unwrap();
+    let input_hash = PublicValues::hash_input(&buffer);
 
     stdin.write_vec(buffer);
 
     // Only execute the program.
-    let (mut public_values, execution_report) = client.execute(&pk.elf, &stdin).run().unwrap();
-
-    // Read the block hash.
-    let block_hash = public_values.read::<B256>();
-    println!("success: block_hash={block_hash}");
+    let (public_values, execution_report) = client.execute(&pk.elf, &stdin).run().unwrap();
+
+    // Read the public values and check them against the block.
+    let (public_values, extra) = P::decode_public_values(public_values.as_slice())?;
+    let expected = PublicValues::new(
+        chain_id,
+        client_input.genesis.config_hash()?,
+        input_hash,
+        &client_input.current_block.header,
+    );
+    check_public_values(&public_values, &expected)?;
+    println!("success: block_hash={}, {extra:?}", public_values.block_hash);
 
     hooks
         .on_execution_end(client_input.current_block.number, &client_input, &execution_report)
@@ -299,6 +311,192 @@ async fn execute_client<P: NodePrimitives, H: ExecutionHooks>(
     Ok(())
 }
 
+/// Executes the consecutive blocks from `first_block_number` to `last_block_number` of an
+/// Ethereum chain in a single run of the range client program, which shares one witness between
+/// the blocks.
+pub async fn execute_range<P>(
+    elf: &[u8],
+    provider: P,
+    config: &Config,
+    first_block_number: u64,
+    last_block_number: u64,
+) -> eyre::Result<()>
+where
+    P: Provider<Ethereum> + Clone,
+{
+    let host_executor: EthHostExecutor = create_eth_host_executor(&config.genesis);
+    let rpc_db = RpcDb::new(provider.clone(), first_block_number - 1);
+
+    // Execute the host.
+    let client_input = host_executor
+        .execute_range(
+            first_block_number,
+            last_block_number,
+            &rpc_db,
+            &provider,
+            config.genesis.clone(),
+            config.custom_beneficiary,
+        )
+        .await?;
+    tracing::info!("witness composition:\n{}", client_input.parent_state.stats());
+
+    let client = EnvProver::new();
+    let (pk, _) = client.setup(elf);
+
+    // Execute the blocks inside the zkVM.
+    let mut stdin = SP1Stdin::new();
+    // The client programs read the witness as a flat list of nodes.
+    let program_input = client_input.clone().map_state(|state| NodeStore::from(&state));
+    let buffer = bincode::serialize(&program_input).unwrap();
+    let input_hash = PublicValues::hash_input(&buffer);
+
+    stdin.write_vec(buffer);
+
+    let (public_values, execution_report) = client.execute(&pk.elf, &stdin).run().unwrap();
+
+    // Read the public values and check them against the blocks.
+    let public_values =
+        RangePublicValues::abi_decode(public_values.as_slice()).map_err(HostError::from)?;
+    let headers = client_input.blocks.iter().map(|block| block.header.clone()).collect::<Vec<_>>();
+    let expected = RangePublicValues::new(
+        config.chain.id(),
+        config.genesis.config_hash()?,
+        input_hash,
+        &headers,
+    );
+    if public_values != expected {
+        return Err(HostError::RangePublicValuesMismatch(
+            Box::new(public_values),
+            Box::new(expected),
+        )
+        .into());
+    }
+    println!(
+        "success: parent_hash={}, block_hash={}, cycles={}",
+        public_values.parent_hash,
+        public_values.block_hash,
+        execution_report.total_instruction_count()
+    );
+
+    if config.prove {
+        println!("Starting proof generation.");
+
+        client.prove(&pk, &stdin).compressed().run().expect("Proving should work.");
+    }
+
+    Ok(())
+}
+
+/// Proves the consecutive blocks from `first_block_number` to `last_block_number` with the client
+/// program, and verifies the compressed proofs in a single run of the aggregation program.
+#[allow(clippy::too_many_arguments)]
+pub async fn aggregate<P, N, NP, F>(
+    client_elf: &[u8],
+    aggregation_elf: &[u8],
+    provider: P,
+    host_executor: HostExecutor<F>,
+    config: &Config,
+    first_block_number: u64,
+    last_block_number: u64,
+) -> eyre::Result<()>
+where
+    P: Provider<N> + Clone,
+    N: Network,
+    NP: NodePrimitives + IntoPrimitives<N> + IntoInput + PublicValuesDecoder,
+    F: BlockExecutionStrategyFactory<Primitives = NP>,
+{
+    if first_block_number > last_block_number {
+        return Err(HostError::InvalidBlockRange(first_block_number, last_block_number).into());
+    }
+
+    let client = EnvProver::new();
+    let (client_pk, client_vk) = client.setup(client_elf);
+    let (aggregation_pk, _) = client.setup(aggregation_elf);
+
+    // Prove the blocks.
+    let mut blocks = Vec::new();
+    let mut block_proofs = Vec::new();
+    for block_number in first_block_number..=last_block_number {
+        let rpc_db = RpcDb::new(provider.clone(), block_number - 1);
+        let client_input = host_executor
+            .execute(
+                block_number,
+                &rpc_db,
+                &provider,
+                config.genesis.clone(),
+                config.custom_beneficiary,
+            )
+            .await?;
+
+        let mut stdin = SP1Stdin::new();
+        // The client programs read the witness as a flat list of nodes.
+        let program_input = client_input.clone().map_state(|state| NodeStore::from(&state));
+        let buffer = bincode::serialize(&program_input).unwrap();
+        let input_hash = PublicValues::hash_input(&buffer);
+
+        stdin.write_vec(buffer);
+
+        println!("Starting proof generation of block {block_number}.");
+        let proof =
+            client.prove(&client_pk, &stdin).compressed().run().expect("Proving should work.");
+
+        let (public_values, _) = NP::decode_public_values(proof.public_values.as_slice())?;
+        let expected = PublicValues::new(
+            config.chain.id(),
+            client_input.genesis.config_hash()?,
+            input_hash,
+            &client_input.current_block.header,
+        );
+        check_public_values(&public_values, &expected)?;
+
+        blocks.push(public_values);
+        block_proofs.push(proof);
+    }
+
+    // Verify the proofs of the blocks inside the zkVM.
+    let mut stdin = SP1Stdin::new();
+    stdin.write(&client_vk.hash_u32());
+    stdin.write(&block_proofs.iter().map(|proof| proof.public_values.to_vec()).collect::<Vec<_>>());
+    for proof in block_proofs {
+        let SP1Proof::Compressed(proof) = proof.proof else {
+            unreachable!("the proofs of the blocks are compressed")
+        };
+        stdin.write_proof(*proof, client_vk.vk.clone());
+    }
+
+    let (public_values, execution_report) =
+        client.execute(&aggregation_pk.elf, &stdin).run().unwrap();
+
+    // Read the public values and check them against the blocks.
+    let public_values =
+        AggregationPublicValues::abi_decode(public_values.as_slice()).map_err(HostError::from)?;
+    let expected = AggregationPublicValues::new(
+        AggregationPublicValues::vkey_hash(&client_vk.hash_u32()),
+        &blocks,
+    )?;
+    if public_values != expected {
+        return Err(HostError::AggregationPublicValuesMismatch(
+            Box::new(public_values),
+            Box::new(expected),
+        )
+        .into());
+    }
+    println!(
+        "success: parent_hash={}, block_hash={}, cycles={}",
+        public_values.parent_hash,
+        public_values.block_hash,
+        execution_report.total_instruction_count()
+    );
+
+    if config.prove {
+        println!("Starting proof generation.");
+
+        client.prove(&aggregation_pk, &stdin).compressed().run().expect("Proving should work.");
+    }
+
+    Ok(())
+}
+
 fn try_load_input_from_cache<P: NodePrimitives + DeserializeOwned>(
     cache_dir: &Path,
     chain_id: u64,
@@ -1,7 +1,7 @@
-use std::{collections::BTreeSet, sync::Arc};
+use std::{collections::BTreeSet, marker::PhantomData, sync::Arc};
 
 use alloy_consensus::{BlockHeader, Header, TxReceipt};
-use alloy_primitives::{Bloom, Sealable};
+use alloy_primitives::{map::HashMap, Bloom, Sealable};
 use alloy_provider::{Network, Provider};
 use alloy_rpc_types::BlockTransactionsKind;
 use reth_chainspec::ChainSpec;
@@ -10,21 +10,24 @@ use reth_evm_ethereum::execute::EthExecutionStrategyFactory;
 use reth_execution_types::ExecutionOutcome;
 use reth_optimism_chainspec::OpChainSpec;
 use reth_optimism_evm::{BasicOpReceiptBuilder, OpExecutionStrategyFactory};
+use reth_optimism_forks::OpHardfork;
 use reth_optimism_primitives::OpPrimitives;
 use reth_primitives_traits::{Block, BlockBody};
-use reth_trie::KeccakKeyHasher;
-use revm::db::{states::bundle_state::BundleRetention, CacheDB};
-use revm_primitives::{Address, B256};
+use reth_trie::{AccountProof, KeccakKeyHasher};
+use revm::db::{states::bundle_state::BundleRetention, BundleState, CacheDB};
+use revm_primitives::{Address, B256, U256};
 use rsp_client_executor::{
+    clique,
     custom::{CustomEthEvmConfig, CustomOpEvmConfig},
-    io::ClientExecutorInput,
+    io::{ClientExecutorInput, ClientExecutorRangeInput},
+    optimism::{self, L2_TO_L1_MESSAGE_PASSER_ADDRESS},
     IntoInput, IntoPrimitives,
 };
-use rsp_mpt::EthereumState;
+use rsp_mpt::{EthereumState, MptStateCommitment};
 use rsp_primitives::{account_proof::eip1186_proof_to_account_proof, genesis::Genesis};
 use rsp_rpc_db::RpcDb;
 
-use crate::HostError;
+use crate::{node_resolver::RpcNodeResolver, HostError};
 
 pub type EthHostExecutor = HostExecutor<EthExecutionStrategyFactory<CustomEthEvmConfig>>;
 
@@ -32,38 +35,48 @@ pub type OpHostExecutor =
     HostExecutor<OpExecutionStrategyFactory<OpPrimitives, OpChainSpec, CustomOpEvmConfig>>;
 
 /// An executor that fetches data from a [Provider] to execute blocks in the [ClientExecutor].
+///
+/// The witness is built for the state commitment `S`.
 #[derive(Debug, Clone)]
-pub struct HostExecutor<F: BlockExecutionStrategyFactory> {
+pub struct HostExecutor<F: BlockExecutionStrategyFactory, S = EthereumState> {
     block_execution_strategy_factory: F,
+    /// The signers of the blocks if the chain uses Clique, shared with the EVM configuration of
+    /// the factory, see [clique::SignerCache].
+    clique: Option<clique::SignerCache>,
+    phantom: PhantomData<S>,
 }
 
 impl EthHostExecutor {
-    pub fn eth(chain_spec: Arc<ChainSpec>, custom_beneficiary: Option<Address>) -> Self {
-        Self {
-            block_execution_strategy_factory: EthExecutionStrategyFactory::new(
-                chain_spec.clone(),
-                CustomEthEvmConfig::eth(chain_spec, custom_beneficiary),
-            ),
-        }
+    pub fn eth(chain_spec: Arc<ChainSpec>) -> Self {
+        let evm_config = CustomEthEvmConfig::eth(chain_spec.clone());
+        let clique = evm_config.clique_signers().cloned();
+
+        Self::new(EthExecutionStrategyFactory::new(chain_spec, evm_config), clique)
     }
 }
 
 impl OpHostExecutor {
     pub fn optimism(chain_spec: Arc<reth_optimism_chainspec::OpChainSpec>) -> Self {
-        Self {
-            block_execution_strategy_factory: OpExecutionStrategyFactory::new(
-                chain_spec.clone(),
-                CustomOpEvmConfig::optimism(chain_spec),
-                BasicOpReceiptBuilder::default(),
-            ),
-        }
+        let block_execution_strategy_factory = OpExecutionStrategyFactory::new(
+            chain_spec.clone(),
+            CustomOpEvmConfig::optimism(chain_spec),
+            BasicOpReceiptBuilder::default(),
+        );
+
+        Self::new(block_execution_strategy_factory, None)
     }
 }
 
-impl<F: BlockExecutionStrategyFactory> HostExecutor<F> {
+/// The maximum number of times a malformed account proof is refetched from the provider.
+const MAX_PROOF_RETRIES: usize = 3;
+
+impl<F: BlockExecutionStrategyFactory, S: MptStateCommitment + Clone> HostExecutor<F, S> {
     /// Creates a new [HostExecutor].
-    pub fn new(block_execution_strategy_factory: F) -> Self {
-        Self { block_execution_strategy_factory }
+    ///
+    /// If the chain uses Clique, `clique` must be the signers of the EVM configuration of the
+    /// factory, so that the block is executed with the recovered signer.
+    pub fn new(block_execution_strategy_factory: F, clique: Option<clique::SignerCache>) -> Self {
+        Self { block_execution_strategy_factory, clique, phantom: PhantomData }
     }
 
     /// Executes the block with the given block number.
@@ -74,7 +87,7 @@ impl<F: BlockExecutionStrategyFactory> HostExecutor<F> {
         provider: &P,
         genesis: Genesis,
         custom_beneficiary: Option<Address>,
-    ) -> Result<ClientExecutorInput<F::Primitives>, HostError>
+    ) -> Result<ClientExecutorInput<F::Primitives, S>, HostError>
     where
         F::Primitives: IntoPrimitives<N> + IntoInput,
         P: Provider<N> + Clone,
@@ -94,6 +107,14 @@ impl<F: BlockExecutionStrategyFactory> HostExecutor<F> {
             .ok_or(HostError::ExpectedBlock(block_number))
             .map(F::Primitives::into_primitive_block)?;
 
+        // Check the supplied beneficiary against the one the block is executed with.
+        let coinbase = self.coinbase(current_block.header())?;
+        if let Some(beneficiary) = custom_beneficiary {
+            if beneficiary != coinbase {
+                return Err(HostError::BeneficiaryMismatch(beneficiary, coinbase));
+            }
+        }
+
         // Setup the database for the block executor.
         tracing::info!("setting up the database for the block executor");
         let cache_db = CacheDB::new(rpc_db);
@@ -138,6 +159,11 @@ impl<F: BlockExecutionStrategyFactory> HostExecutor<F> {
             logs_bloom.accrue_bloom(&r.bloom());
         });
 
+        // The requests hash was checked against the header by `validate_block_post_execution`,
+        // it is only committed to from Prague (EIP-7685).
+        let requests_hash =
+            current_block.header().requests_hash().map(|_| requests.requests_hash());
+
         // Convert the output to an execution outcome.
         let executor_outcome = ExecutionOutcome::new(
             state.take_bundle(),
@@ -146,57 +172,53 @@ impl<F: BlockExecutionStrategyFactory> HostExecutor<F> {
             vec![requests],
         );
 
-        let state_requests = rpc_db.get_state_requests();
-
-        // For every account we touched, fetch the storage proofs for all the slots we touched.
-        tracing::info!("fetching storage proofs");
-        let mut before_storage_proofs = Vec::new();
-        let mut after_storage_proofs = Vec::new();
-
-        for (address, used_keys) in state_requests.iter() {
-            let modified_keys = executor_outcome
-                .state()
-                .state
-                .get(address)
-                .map(|account| {
-                    account.storage.keys().map(|key| B256::from(*key)).collect::<BTreeSet<_>>()
-                })
-                .unwrap_or_default()
-                .into_iter()
-                .collect::<Vec<_>>();
-
-            let keys = used_keys
-                .iter()
-                .map(|key| B256::from(*key))
-                .chain(modified_keys.clone().into_iter())
-                .collect::<BTreeSet<_>>()
-                .into_iter()
-                .collect::<Vec<_>>();
-
-            let storage_proof = provider
-                .get_proof(*address, keys.clone())
-                .block_id((block_number - 1).into())
-                .await?;
-            before_storage_proofs.push(eip1186_proof_to_account_proof(storage_proof));
-
-            let storage_proof =
-                provider.get_proof(*address, modified_keys).block_id((block_number).into()).await?;
-            after_storage_proofs.push(eip1186_proof_to_account_proof(storage_proof));
+        // The account of the L2ToL1MessagePasser must be part of the witness of OP Stack blocks, as
+        // its storage root is committed to by the output root and, from Isthmus on, by the
+        // withdrawals root.
+        let op_stack = genesis.is_optimism();
+        let isthmus = op_stack
+            && OpChainSpec::try_from(&genesis).is_ok_and(|chain_spec| {
+                chain_spec
+                    .fork(OpHardfork::Isthmus)
+                    .active_at_timestamp(current_block.header().timestamp())
+            });
+
+        let mut state_requests = rpc_db.get_state_requests();
+        if op_stack {
+            state_requests.entry(L2_TO_L1_MESSAGE_PASSER_ADDRESS).or_default();
         }
 
-        let state = EthereumState::from_transition_proofs(
+        // For every account we touched, fetch the storage proofs for all the slots we touched.
+        let modified_keys = modified_storage_keys([executor_outcome.state()]);
+        let mut state = fetch_witness::<S, P, N>(
+            provider,
+            block_number - 1,
+            block_number,
             previous_block.header().state_root(),
-            &before_storage_proofs.iter().map(|item| (item.address, item.clone())).collect(),
-            &after_storage_proofs.iter().map(|item| (item.address, item.clone())).collect(),
-        )?;
+            &state_requests,
+            &modified_keys,
+        )
+        .await?;
 
-        // Verify the state root.
+        // Verify the state root, fetching the trie nodes that are not part of the proofs, such as
+        // the siblings of branches that collapse after a deletion.
         tracing::info!("verifying the state root");
-        let state_root = {
+        let hashed_post_state = executor_outcome.hash_state_slow::<KeccakKeyHasher>();
+        let mut node_resolver = RpcNodeResolver::default();
+        let (state_root, withdrawals_root) = loop {
             let mut mutated_state = state.clone();
-            mutated_state.update(&executor_outcome.hash_state_slow::<KeccakKeyHasher>());
-            mutated_state.state_root()
+            let result = mutated_state.update_with_resolver(&hashed_post_state, &node_resolver);
+            if node_resolver.fetch_missing(provider).await == 0 {
+                result?;
+                let withdrawals_root = if isthmus {
+                    Some(optimism::message_passer_storage_root(&mutated_state)?)
+                } else {
+                    current_block.header().withdrawals_root()
+                };
+                break (mutated_state.state_root(), withdrawals_root);
+            }
         };
+        state.splice_nodes(node_resolver.into_nodes());
         if state_root != current_block.header().state_root() {
             return Err(HostError::StateRootMismatch(
                 state_root,
@@ -224,11 +246,11 @@ impl<F: BlockExecutionStrategyFactory> HostExecutor<F> {
             mix_hash: current_block.header().mix_hash().unwrap(),
             nonce: current_block.header().nonce().unwrap(),
             base_fee_per_gas: current_block.header().base_fee_per_gas(),
-            withdrawals_root: current_block.header().withdrawals_root(),
+            withdrawals_root,
             blob_gas_used: current_block.header().blob_gas_used(),
             excess_blob_gas: current_block.header().excess_blob_gas(),
             parent_beacon_block_root: current_block.header().parent_beacon_block_root(),
-            requests_hash: None,
+            requests_hash,
         };
 
         // Assert the derived header is correct.
@@ -247,21 +269,171 @@ impl<F: BlockExecutionStrategyFactory> HostExecutor<F> {
         );
 
         // Fetch the parent headers needed to constrain the BLOCKHASH opcode.
-        let oldest_ancestor = *rpc_db.oldest_ancestor.borrow();
-        let mut ancestor_headers = vec![];
-        tracing::info!("fetching {} ancestor headers", block_number - oldest_ancestor);
-        for height in (oldest_ancestor..=(block_number - 1)).rev() {
+        let ancestor_headers =
+            fetch_ancestor_headers::<F::Primitives, P, N>(provider, rpc_db, block_number - 1)
+                .await?;
+
+        // Create the client input.
+        let client_input = ClientExecutorInput {
+            current_block: F::Primitives::into_input_block(current_block),
+            ancestor_headers,
+            parent_state: state,
+            state_requests,
+            bytecodes: rpc_db.get_bytecodes(),
+            genesis,
+            custom_beneficiary,
+        };
+        tracing::info!("successfully generated client input");
+
+        Ok(client_input)
+    }
+
+    /// Executes the consecutive blocks from `first_block_number` to `last_block_number` and
+    /// builds a single witness covering the state touched by all of them.
+    ///
+    /// The `rpc_db` must read the state as of the parent of the first block.
+    pub async fn execute_range<'a, P, N>(
+        &self,
+        first_block_number: u64,
+        last_block_number: u64,
+        rpc_db: &'a RpcDb<P, N>,
+        provider: &P,
+        genesis: Genesis,
+        custom_beneficiary: Option<Address>,
+    ) -> Result<ClientExecutorRangeInput<F::Primitives, S>, HostError>
+    where
+        F::Primitives: IntoPrimitives<N> + IntoInput,
+        P: Provider<N> + Clone,
+        N: Network,
+    {
+        if first_block_number > last_block_number {
+            return Err(HostError::InvalidBlockRange(first_block_number, last_block_number));
+        }
+
+        // Fetch the blocks from the provider.
+        tracing::info!("fetching blocks {} to {}", first_block_number, last_block_number);
+        let mut blocks = Vec::new();
+        for block_number in first_block_number..=last_block_number {
             let block = provider
-                .get_block_by_number(height.into(), BlockTransactionsKind::Hashes)
+                .get_block_by_number(block_number.into(), BlockTransactionsKind::Full)
                 .await?
-                .ok_or(HostError::ExpectedBlock(height))?;
+                .ok_or(HostError::ExpectedBlock(block_number))
+                .map(F::Primitives::into_primitive_block)?;
+            blocks.push(block);
+        }
 
-            ancestor_headers.push(F::Primitives::into_primitive_header(block))
+        let previous_block = provider
+            .get_block_by_number((first_block_number - 1).into(), BlockTransactionsKind::Hashes)
+            .await?
+            .ok_or(HostError::ExpectedBlock(first_block_number - 1))
+            .map(F::Primitives::into_primitive_header)?;
+
+        // Execute the blocks in sequence with the same EVM state, which keeps the changes of the
+        // previous blocks, and fetch all the necessary data along the way.
+        let cache_db = CacheDB::new(rpc_db);
+        let mut strategy = self.block_execution_strategy_factory.create_strategy(cache_db);
+        let mut executor_outcomes = Vec::with_capacity(blocks.len());
+
+        for current_block in blocks.iter() {
+            // Check the supplied beneficiary against the one the block is executed with.
+            let coinbase = self.coinbase(current_block.header())?;
+            if let Some(beneficiary) = custom_beneficiary {
+                if beneficiary != coinbase {
+                    return Err(HostError::BeneficiaryMismatch(beneficiary, coinbase));
+                }
+            }
+
+            tracing::info!(
+                "executing the block and with rpc db: block_number={}, transaction_count={}",
+                current_block.header().number(),
+                current_block.body().transactions().len()
+            );
+
+            let executor_block_input = current_block
+                .clone()
+                .try_into_recovered()
+                .map_err(|_| HostError::FailedToRecoverSenders)?;
+
+            strategy.apply_pre_execution_changes(&executor_block_input)?;
+
+            let executor_output = strategy.execute_transactions(&executor_block_input)?;
+
+            let requests = strategy
+                .apply_post_execution_changes(&executor_block_input, &executor_output.receipts)?;
+
+            strategy.validate_block_post_execution(
+                &executor_block_input,
+                &executor_output.receipts,
+                &requests,
+            )?;
+
+            strategy.state_mut().merge_transitions(BundleRetention::Reverts);
+            executor_outcomes.push(ExecutionOutcome::new(
+                strategy.state_mut().take_bundle(),
+                vec![executor_output.receipts],
+                current_block.header().number(),
+                vec![requests],
+            ));
         }
 
+        let mut state_requests = rpc_db.get_state_requests();
+        if genesis.is_optimism() {
+            state_requests.entry(L2_TO_L1_MESSAGE_PASSER_ADDRESS).or_default();
+        }
+
+        // Fetch the proofs of all the touched slots as of the parent of the first block.
+        let modified_keys =
+            modified_storage_keys(executor_outcomes.iter().map(|outcome| outcome.state()));
+        let mut state = fetch_witness::<S, P, N>(
+            provider,
+            first_block_number - 1,
+            last_block_number,
+            previous_block.state_root(),
+            &state_requests,
+            &modified_keys,
+        )
+        .await?;
+
+        // Verify the state root of every block, fetching the trie nodes that are not part of the
+        // proofs.
+        tracing::info!("verifying the state roots");
+        let mut node_resolver = RpcNodeResolver::default();
+        let mut post_state = state.clone();
+        for (current_block, executor_outcome) in blocks.iter().zip(executor_outcomes.iter()) {
+            let hashed_post_state = executor_outcome.hash_state_slow::<KeccakKeyHasher>();
+            post_state = loop {
+                let mut mutated_state = post_state.clone();
+                let result = mutated_state.update_with_resolver(&hashed_post_state, &node_resolver);
+                if node_resolver.fetch_missing(provider).await == 0 {
+                    result?;
+                    break mutated_state;
+                }
+            };
+
+            let state_root = post_state.state_root();
+            if state_root != current_block.header().state_root() {
+                return Err(HostError::StateRootMismatch(
+                    state_root,
+                    current_block.header().state_root(),
+                ));
+            }
+        }
+        state.splice_nodes(node_resolver.into_nodes());
+
+        tracing::info!(
+            "successfully executed blocks: first_block_number={}, last_block_number={}",
+            first_block_number,
+            last_block_number
+        );
+
+        // Fetch the parent headers needed to constrain the BLOCKHASH opcode.
+        let ancestor_headers =
+            fetch_ancestor_headers::<F::Primitives, P, N>(provider, rpc_db, first_block_number - 1)
+                .await?;
+
         // Create the client input.
-        let client_input = ClientExecutorInput {
-            current_block: F::Primitives::into_input_block(current_block),
+        let client_input = ClientExecutorRangeInput {
+            blocks: blocks.into_iter().map(F::Primitives::into_input_block).collect(),
             ancestor_headers,
             parent_state: state,
             state_requests,
@@ -273,4 +445,171 @@ impl<F: BlockExecutionStrategyFactory> HostExecutor<F> {
 
         Ok(client_input)
     }
+
+    /// Returns the address credited with the block rewards and fees: the signer of the block on
+    /// Clique chains, its beneficiary otherwise.
+    fn coinbase<H: BlockHeader>(&self, header: &H) -> Result<Address, HostError> {
+        match &self.clique {
+            Some(signers) => signers.recover(header).ok_or(HostError::InvalidCliqueSeal),
+            None => Ok(header.beneficiary()),
+        }
+    }
+}
+
+/// Fetches the headers from `parent_number` down to the oldest block whose hash was read by the
+/// execution.
+async fn fetch_ancestor_headers<NP, P, N>(
+    provider: &P,
+    rpc_db: &RpcDb<P, N>,
+    parent_number: u64,
+) -> Result<Vec<Header>, HostError>
+where
+    NP: IntoPrimitives<N>,
+    P: Provider<N> + Clone,
+    N: Network,
+{
+    let oldest_ancestor = *rpc_db.oldest_ancestor.borrow();
+    let mut ancestor_headers = vec![];
+    tracing::info!("fetching {} ancestor headers", parent_number + 1 - oldest_ancestor);
+    for height in (oldest_ancestor..=parent_number).rev() {
+        let block = provider
+            .get_block_by_number(height.into(), BlockTransactionsKind::Hashes)
+            .await?
+            .ok_or(HostError::ExpectedBlock(height))?;
+
+        ancestor_headers.push(NP::into_primitive_header(block))
+    }
+
+    Ok(ancestor_headers)
+}
+
+/// Returns the storage slots modified by the blocks whose changes are `bundles`, by account.
+fn modified_storage_keys<'a>(
+    bundles: impl IntoIterator<Item = &'a BundleState>,
+) -> HashMap<Address, BTreeSet<B256>> {
+    let mut modified_keys: HashMap<Address, BTreeSet<B256>> = HashMap::default();
+    for bundle in bundles {
+        for (address, account) in bundle.state.iter() {
+            modified_keys
+                .entry(*address)
+                .or_default()
+                .extend(account.storage.keys().map(|key| B256::from(*key)));
+        }
+    }
+
+    modified_keys
+}
+
+/// Builds the witness of the state as of the block `parent_number` from the proofs of the
+/// `state_requests` and of the `modified_keys` before and after the block `block_number`.
+///
+/// A malformed proof for a single account is refetched rather than failing the whole witness,
+/// since public RPCs occasionally return inconsistent responses.
+async fn fetch_witness<S, P, N>(
+    provider: &P,
+    parent_number: u64,
+    block_number: u64,
+    parent_state_root: B256,
+    state_requests: &HashMap<Address, Vec<U256>>,
+    modified_keys: &HashMap<Address, BTreeSet<B256>>,
+) -> Result<S, HostError>
+where
+    S: MptStateCommitment,
+    P: Provider<N>,
+    N: Network,
+{
+    tracing::info!("fetching storage proofs");
+    let mut proof_keys: HashMap<Address, (Vec<B256>, Vec<B256>)> = HashMap::default();
+    let mut before_storage_proofs = HashMap::default();
+    let mut after_storage_proofs = HashMap::default();
+
+    for (address, used_keys) in state_requests.iter() {
+        let modified_keys = modified_keys
+            .get(address)
+            .map(|keys| keys.iter().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        let keys = used_keys
+            .iter()
+            .map(|key| B256::from(*key))
+            .chain(modified_keys.clone().into_iter())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let (before, after) = fetch_transition_proofs(
+            provider,
+            parent_number,
+            block_number,
+            *address,
+            &keys,
+            &modified_keys,
+        )
+        .await?;
+        before_storage_proofs.insert(*address, before);
+        after_storage_proofs.insert(*address, after);
+        proof_keys.insert(*address, (keys, modified_keys));
+    }
+
+    let mut retries = 0;
+    loop {
+        let err = match S::from_transition_proofs(
+            parent_state_root,
+            &before_storage_proofs,
+            &after_storage_proofs,
+        ) {
+            Ok(state) => return Ok(state),
+            Err(err) => err,
+        };
+
+        let Some((address, (keys, modified_keys))) =
+            err.address().and_then(|address| proof_keys.get_key_value(&address))
+        else {
+            return Err(err.into());
+        };
+        if retries >= MAX_PROOF_RETRIES {
+            return Err(err.into());
+        }
+        retries += 1;
+
+        tracing::warn!(
+            "invalid proof for account {}, refetching ({}/{}): {}",
+            address,
+            retries,
+            MAX_PROOF_RETRIES,
+            err
+        );
+        let (before, after) = fetch_transition_proofs(
+            provider,
+            parent_number,
+            block_number,
+            *address,
+            keys,
+            modified_keys,
+        )
+        .await?;
+        before_storage_proofs.insert(*address, before);
+        after_storage_proofs.insert(*address, after);
+    }
+}
+
+/// Fetches the proofs of `address` at the block `parent_number` for `keys` and at `block_number`
+/// for `modified_keys`.
+async fn fetch_transition_proofs<P, N>(
+    provider: &P,
+    parent_number: u64,
+    block_number: u64,
+    address: Address,
+    keys: &[B256],
+    modified_keys: &[B256],
+) -> Result<(AccountProof, AccountProof), HostError>
+where
+    P: Provider<N>,
+    N: Network,
+{
+    let before = provider.get_proof(address, keys.to_vec()).block_id(parent_number.into()).await?;
+    let after =
+        provider.get_proof(address, modified_keys.to_vec()).block_id(block_number.into()).await?;
+
+    Ok((eip1186_proof_to_account_proof(before), eip1186_proof_to_account_proof(after)))
 }
@@ -1,12 +1,8 @@
 use alloy_chains::Chain;
 pub use error::Error as HostError;
 use reth_chainspec::ChainSpec;
-use reth_evm_ethereum::execute::EthExecutionStrategyFactory;
 use reth_optimism_chainspec::OpChainSpec;
-use reth_optimism_evm::{BasicOpReceiptBuilder, OpExecutionStrategyFactory};
-use reth_optimism_primitives::OpPrimitives;
 use revm_primitives::Address;
-use rsp_client_executor::custom::{CustomEthEvmConfig, CustomOpEvmConfig};
 use rsp_primitives::genesis::Genesis;
 use std::{path::PathBuf, sync::Arc};
 use url::Url;
@@ -14,7 +10,9 @@ use url::Url;
 mod error;
 
 mod full_executor;
-pub use full_executor::{build_executor, BlockExecutor, EitherExecutor, FullExecutor};
+pub use full_executor::{
+    aggregate, build_executor, execute_range, BlockExecutor, EitherExecutor, FullExecutor,
+};
 
 mod hooks;
 pub use hooks::{ExecutionHooks, NoopExecutionHooks};
@@ -22,28 +20,22 @@ pub use hooks::{ExecutionHooks, NoopExecutionHooks};
 mod host_executor;
 pub use host_executor::{EthHostExecutor, HostExecutor, OpHostExecutor};
 
-pub fn create_eth_block_execution_strategy_factory(
-    genesis: &Genesis,
-    custom_beneficiary: Option<Address>,
-) -> EthExecutionStrategyFactory<CustomEthEvmConfig> {
+mod node_resolver;
+pub use node_resolver::RpcNodeResolver;
+
+mod public_values;
+pub use public_values::{check_public_values, PublicValuesDecoder};
+
+pub fn create_eth_host_executor(genesis: &Genesis) -> EthHostExecutor {
     let chain_spec: Arc<ChainSpec> = Arc::new(genesis.try_into().unwrap());
 
-    EthExecutionStrategyFactory::new(
-        chain_spec.clone(),
-        CustomEthEvmConfig::eth(chain_spec, custom_beneficiary),
-    )
+    EthHostExecutor::eth(chain_spec)
 }
 
-pub fn create_op_block_execution_strategy_factory(
-    genesis: &Genesis,
-) -> OpExecutionStrategyFactory<OpPrimitives, OpChainSpec, CustomOpEvmConfig> {
+pub fn create_op_host_executor(genesis: &Genesis) -> OpHostExecutor {
     let chain_spec: Arc<OpChainSpec> = Arc::new(genesis.try_into().unwrap());
 
-    OpExecutionStrategyFactory::new(
-        chain_spec.clone(),
-        CustomOpEvmConfig::optimism(chain_spec),
-        BasicOpReceiptBuilder::default(),
-    )
+    OpHostExecutor::optimism(chain_spec)
 }
 
 #[derive(Debug)]
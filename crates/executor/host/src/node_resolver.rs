@@ -0,0 +1,69 @@
+use std::{collections::BTreeSet, sync::Mutex};
+
+use alloy_primitives::{map::HashMap, Bytes, B256};
+use alloy_provider::{Network, Provider};
+use rsp_mpt::{MptNode, NodeResolver};
+
+/// A [NodeResolver] backed by the `debug_dbGet` RPC method.
+///
+/// Resolving happens in rounds: the digests that could not be resolved while applying a state
+/// update are recorded, fetched from the provider with [RpcNodeResolver::fetch_missing], and the
+/// update is applied again until no new node is needed.
+#[derive(Debug, Default)]
+pub struct RpcNodeResolver {
+    nodes: HashMap<B256, MptNode>,
+    missing: Mutex<BTreeSet<B256>>,
+    unavailable: BTreeSet<B256>,
+}
+
+impl RpcNodeResolver {
+    /// Fetches the nodes that were requested but not known, and returns the number of nodes
+    /// that were added.
+    ///
+    /// Nodes that the provider cannot return are not requested again.
+    pub async fn fetch_missing<P, N>(&mut self, provider: &P) -> usize
+    where
+        P: Provider<N>,
+        N: Network,
+    {
+        let missing = std::mem::take(self.missing.get_mut().unwrap());
+        let mut fetched = 0;
+
+        for digest in missing {
+            let node = provider
+                .raw_request::<_, Bytes>("debug_dbGet".into(), (digest,))
+                .await
+                .map_err(|err| err.to_string())
+                .and_then(|bytes| MptNode::decode(bytes).map_err(|err| err.to_string()));
+
+            match node {
+                Ok(node) => {
+                    self.nodes.insert(digest, node);
+                    fetched += 1;
+                }
+                Err(err) => {
+                    tracing::warn!("failed to fetch trie node {}: {}", digest, err);
+                    self.unavailable.insert(digest);
+                }
+            }
+        }
+
+        fetched
+    }
+
+    /// Returns the nodes fetched so far.
+    pub fn into_nodes(self) -> impl Iterator<Item = MptNode> {
+        self.nodes.into_values()
+    }
+}
+
+impl NodeResolver for RpcNodeResolver {
+    fn resolve(&self, digest: B256) -> Option<MptNode> {
+        let node = self.nodes.get(&digest).cloned();
+        if node.is_none() && !self.unavailable.contains(&digest) {
+            self.missing.lock().unwrap().insert(digest);
+        }
+
+        node
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt::Debug;
+
+use reth_optimism_primitives::OpPrimitives;
+use reth_primitives::{EthPrimitives, NodePrimitives};
+use rsp_client_executor::public_values::{OpPublicValues, PublicValues};
+
+use crate::HostError;
+
+/// Decodes the public values committed by the client program of the chain.
+pub trait PublicValuesDecoder: NodePrimitives {
+    /// The public values the client program commits after the [PublicValues].
+    type Extra: Debug;
+
+    /// Decodes the [PublicValues] and the chain specific public values that follow them.
+    fn decode_public_values(data: &[u8]) -> Result<(PublicValues, Self::Extra), HostError> {
+        if data.len() < PublicValues::ABI_ENCODED_SIZE {
+            return Err(HostError::InvalidPublicValuesLength(data.len()));
+        }
+        let (public_values, extra) = data.split_at(PublicValues::ABI_ENCODED_SIZE);
+
+        Ok((PublicValues::abi_decode(public_values)?, Self::decode_extra(extra)?))
+    }
+
+    /// Decodes the chain specific public values.
+    fn decode_extra(data: &[u8]) -> Result<Self::Extra, HostError>;
+}
+
+impl PublicValuesDecoder for EthPrimitives {
+    type Extra = ();
+
+    fn decode_extra(data: &[u8]) -> Result<Self::Extra, HostError> {
+        if !data.is_empty() {
+            return Err(HostError::InvalidPublicValuesLength(
+                PublicValues::ABI_ENCODED_SIZE + data.len(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl PublicValuesDecoder for OpPrimitives {
+    type Extra = OpPublicValues;
+
+    fn decode_extra(data: &[u8]) -> Result<Self::Extra, HostError> {
+        if data.len() != OpPublicValues::ABI_ENCODED_SIZE {
+            return Err(HostError::InvalidPublicValuesLength(
+                PublicValues::ABI_ENCODED_SIZE + data.len(),
+            ));
+        }
+
+        Ok(OpPublicValues::abi_decode(data)?)
+    }
+}
+
+/// Checks the decoded public values against the ones expected for the block.
+pub fn check_public_values(
+    public_values: &PublicValues,
+    expected: &PublicValues,
+) -> Result<(), HostError> {
+    if public_values != expected {
+        return Err(HostError::PublicValuesMismatch(Box::new(*public_values), Box::new(*expected)));
+    }
+
+    Ok(())
+}
@@ -1,16 +1,19 @@
 use std::sync::Arc;
 
-use alloy_provider::{network::Ethereum, Network, RootProvider};
+use alloy_provider::{network::Ethereum, Network, Provider, RootProvider};
 use reth_chainspec::ChainSpec;
 use reth_evm::execute::BlockExecutionStrategyFactory;
 use reth_optimism_chainspec::OpChainSpec;
 use revm_primitives::{address, Address};
 use rsp_client_executor::{
+    error::ClientError,
     executor::{ClientExecutor, EthClientExecutor},
-    io::ClientExecutorInput,
+    io::{ClientExecutorInput, EthClientExecutorRangeInput, NodeStore},
+    public_values::PublicValues,
     FromInput, IntoInput, IntoPrimitives,
 };
 use rsp_host_executor::{EthHostExecutor, HostExecutor};
+use rsp_mpt::EthereumState;
 use rsp_primitives::genesis::Genesis;
 use rsp_rpc_db::RpcDb;
 use serde::{de::DeserializeOwned, Serialize};
@@ -25,28 +28,30 @@ async fn test_e2e_ethereum() {
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_e2e_optimism() {
-    let chain_spec: Arc<OpChainSpec> = Arc::new((&Genesis::OpMainnet).try_into().unwrap());
-
-    // Setup the host executor.
-    let host_executor = rsp_host_executor::OpHostExecutor::optimism(chain_spec.clone());
+async fn test_e2e_ethereum_prague() {
+    run_eth_e2e(&Genesis::Mainnet, "RPC_1", 22431084, None).await;
+}
 
-    // Setup the client executor.
-    let client_executor = rsp_client_executor::executor::OpClientExecutor::optimism(chain_spec);
+#[tokio::test(flavor = "multi_thread")]
+async fn test_e2e_optimism() {
+    run_op_e2e(&Genesis::OpMainnet, "RPC_10", 122853660).await;
+}
 
-    run_e2e::<_, op_alloy_network::Optimism>(
-        host_executor,
-        client_executor,
-        "RPC_10",
-        122853660,
-        &Genesis::OpMainnet,
-        None,
-    )
-    .await;
+#[tokio::test(flavor = "multi_thread")]
+async fn test_e2e_optimism_isthmus() {
+    // The withdrawals root is the storage root of the L2ToL1MessagePasser.
+    run_op_e2e(&Genesis::OpMainnet, "RPC_10", 136000000).await;
 }
 
 #[tokio::test(flavor = "multi_thread")]
 async fn test_e2e_linea() {
+    // The beneficiary is recovered from the signature of the block.
+    run_eth_e2e(&Genesis::Linea, "RPC_59144", 5600000, None).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_e2e_linea_with_beneficiary() {
+    // A supplied beneficiary is checked against the signer of the block.
     run_eth_e2e(
         &Genesis::Linea,
         "RPC_59144",
@@ -61,6 +66,132 @@ async fn test_e2e_sepolia() {
     run_eth_e2e(&Genesis::Sepolia, "RPC_11155111", 6804324, None).await;
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_e2e_ethereum_range() {
+    // Initialize the environment variables.
+    dotenv::dotenv().ok();
+    let genesis = Genesis::Mainnet;
+    let chain_spec: Arc<ChainSpec> = Arc::new((&genesis).try_into().unwrap());
+    let host_executor = EthHostExecutor::eth(chain_spec.clone());
+    let client_executor = EthClientExecutor::eth(chain_spec);
+
+    let rpc_url = Url::parse(std::env::var("RPC_1").unwrap().as_str()).expect("invalid rpc url");
+    let provider = RootProvider::<Ethereum>::new_http(rpc_url);
+    let (first_block_number, last_block_number) = (20526624, 20526626);
+    let rpc_db = RpcDb::new(provider.clone(), first_block_number - 1);
+
+    // Execute the host.
+    let client_input = host_executor
+        .execute_range(
+            first_block_number,
+            last_block_number,
+            &rpc_db,
+            &provider,
+            genesis.clone(),
+            None,
+        )
+        .await
+        .expect("failed to execute host");
+
+    // Save the client input to a buffer.
+    let buffer = bincode::serialize(&client_input).unwrap();
+
+    // Execute the client.
+    let public_values = client_executor
+        .execute_range_with_public_values(client_input.clone(), PublicValues::hash_input(&buffer))
+        .expect("failed to execute client");
+    assert_eq!(public_values.parent_hash, client_input.blocks[0].header.parent_hash);
+    assert_eq!(public_values.block_hash, client_input.blocks[2].header.hash_slow());
+    assert_eq!(public_values.last_block_number, last_block_number);
+
+    // Load the client input from a buffer.
+    let _: EthClientExecutorRangeInput = bincode::deserialize(&buffer).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_e2e_ethereum_range_matches_blocks() {
+    run_eth_range_e2e(&Genesis::Mainnet, "RPC_1", 20526624, None).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_e2e_linea_range_with_beneficiary() {
+    // A supplied beneficiary is checked against the signer of every block.
+    run_eth_range_e2e(
+        &Genesis::Linea,
+        "RPC_59144",
+        5600000,
+        Some(address!("8f81e2e3f8b46467523463835f965ffe476e1c9e")),
+    )
+    .await;
+}
+
+/// Executes two consecutive blocks as a range and one by one, and checks that both derive the
+/// same headers and reject the same wrong beneficiary.
+async fn run_eth_range_e2e(
+    genesis: &Genesis,
+    env_var_key: &str,
+    first_block_number: u64,
+    custom_beneficiary: Option<Address>,
+) {
+    // Initialize the environment variables.
+    dotenv::dotenv().ok();
+    let chain_spec: Arc<ChainSpec> = Arc::new(genesis.try_into().unwrap());
+    let host_executor = EthHostExecutor::eth(chain_spec.clone());
+    let client_executor = EthClientExecutor::eth(chain_spec);
+
+    let rpc_url =
+        Url::parse(std::env::var(env_var_key).unwrap().as_str()).expect("invalid rpc url");
+    let provider = RootProvider::<Ethereum>::new_http(rpc_url);
+    let last_block_number = first_block_number + 1;
+
+    // Execute the blocks as a range.
+    let rpc_db = RpcDb::new(provider.clone(), first_block_number - 1);
+    let range_input = host_executor
+        .execute_range(
+            first_block_number,
+            last_block_number,
+            &rpc_db,
+            &provider,
+            genesis.clone(),
+            custom_beneficiary,
+        )
+        .await
+        .expect("failed to execute host");
+    let range_headers =
+        client_executor.execute_range(range_input.clone()).expect("failed to execute client");
+
+    // Execute the blocks one by one.
+    let mut block_inputs = Vec::new();
+    for block_number in first_block_number..=last_block_number {
+        let rpc_db = RpcDb::new(provider.clone(), block_number - 1);
+        let block_input = host_executor
+            .execute(block_number, &rpc_db, &provider, genesis.clone(), custom_beneficiary)
+            .await
+            .expect("failed to execute host");
+        let header =
+            client_executor.execute(block_input.clone()).expect("failed to execute client");
+        assert_eq!(header, range_headers[block_inputs.len()]);
+        block_inputs.push(block_input);
+    }
+
+    // A wrong beneficiary is rejected by both.
+    let wrong_beneficiary = Some(Address::repeat_byte(0x01));
+    let range_input =
+        EthClientExecutorRangeInput { custom_beneficiary: wrong_beneficiary, ..range_input };
+    assert!(matches!(
+        client_executor.execute_range(range_input),
+        Err(ClientError::MismatchedBeneficiary(..))
+    ));
+    for block_input in block_inputs {
+        let block_input =
+            ClientExecutorInput { custom_beneficiary: wrong_beneficiary, ..block_input };
+        assert!(matches!(
+            client_executor.execute(block_input),
+            Err(ClientError::MismatchedBeneficiary(..))
+        ));
+    }
+}
+
 async fn run_eth_e2e(
     genesis: &Genesis,
     env_var_key: &str,
@@ -70,10 +201,10 @@ async fn run_eth_e2e(
     let chain_spec: Arc<ChainSpec> = Arc::new(genesis.try_into().unwrap());
 
     // Setup the host executor.
-    let host_executor = EthHostExecutor::eth(chain_spec.clone(), custom_beneficiary);
+    let host_executor = EthHostExecutor::eth(chain_spec.clone());
 
     // Setup the client executor.
-    let client_executor = EthClientExecutor::eth(chain_spec, custom_beneficiary);
+    let client_executor = EthClientExecutor::eth(chain_spec);
 
     run_e2e::<_, Ethereum>(
         host_executor,
@@ -86,6 +217,26 @@ async fn run_eth_e2e(
     .await;
 }
 
+async fn run_op_e2e(genesis: &Genesis, env_var_key: &str, block_number: u64) {
+    let chain_spec: Arc<OpChainSpec> = Arc::new(genesis.try_into().unwrap());
+
+    // Setup the host executor.
+    let host_executor = rsp_host_executor::OpHostExecutor::optimism(chain_spec.clone());
+
+    // Setup the client executor.
+    let client_executor = rsp_client_executor::executor::OpClientExecutor::optimism(chain_spec);
+
+    run_e2e::<_, op_alloy_network::Optimism>(
+        host_executor,
+        client_executor,
+        env_var_key,
+        block_number,
+        genesis,
+        None,
+    )
+    .await;
+}
+
 async fn run_e2e<F, N>(
     host_executor: HostExecutor<F>,
     client_executor: ClientExecutor<F>,
@@ -120,12 +271,28 @@ async fn run_e2e<F, N>(
         .await
         .expect("failed to execute host");
 
-    // Execute the client.
-    client_executor.execute(client_input.clone()).expect("failed to execute client");
-
     // Save the client input to a buffer.
     let buffer = bincode::serialize(&client_input).unwrap();
 
+    // Execute the client.
+    let public_values = client_executor
+        .execute_with_public_values(client_input.clone(), PublicValues::hash_input(&buffer))
+        .expect("failed to execute client");
+    assert_eq!(public_values.chain_id, provider.get_chain_id().await.unwrap());
+    assert_eq!(public_values.block_number, block_number);
+    assert_eq!(public_values.block_hash, client_input.current_block.header.hash_slow());
+    assert_eq!(public_values.chain_config_hash, genesis.config_hash().unwrap());
+
     // Load the client input from a buffer.
     let _: ClientExecutorInput<F::Primitives> = bincode::deserialize(&buffer).unwrap();
+
+    // Rebuild the witness from the node store the client programs read.
+    let program_input = client_input.clone().map_state(|state| NodeStore::from(&state));
+    let buffer = bincode::serialize(&program_input).unwrap();
+    let program_input: ClientExecutorInput<F::Primitives, NodeStore> =
+        bincode::deserialize(&buffer).unwrap();
+    assert_eq!(
+        EthereumState::try_from(program_input.parent_state).unwrap(),
+        client_input.parent_state
+    );
 }
@@ -0,0 +1,878 @@
+//! A sparse Merkle Patricia Trie stored in a contiguous arena.
+//!
+//! [ArenaTrie] has the same semantics as [MptNode], but all nodes of a trie live in a single
+//! `Vec`, children are referenced by index, paths are stored inline as nibbles and references
+//! are cached in a `Cell`. This avoids most of the allocations of [MptNode], which dominate the
+//! cost of trie updates inside the zkVM.
+
+use alloy_primitives::{map::HashMap, B256};
+use alloy_rlp::Encodable;
+use core::{cell::Cell, mem, num::NonZeroU32};
+use reth_trie::{HashedPostState, TrieAccount};
+use rlp::{DecoderError, Prototype, Rlp};
+
+use crate::{
+    mpt::{keccak, Error, MptNode, MptNodeData, RlpBytes, EMPTY_ROOT},
+    EthereumState, StateUpdateError,
+};
+
+/// The maximum number of nibbles of a key, i.e. the nibbles of a 32-byte hash.
+const MAX_NIBBLES: usize = 64;
+
+/// The index of a node in an [ArenaTrie], offset by one so that `Option<NodeId>` fits in 4 bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct NodeId(NonZeroU32);
+
+impl NodeId {
+    fn index(self) -> usize {
+        self.0.get() as usize - 1
+    }
+}
+
+/// A path of up to [MAX_NIBBLES] nibbles, stored inline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Nibbles {
+    len: u8,
+    nibs: [u8; MAX_NIBBLES],
+}
+
+impl Nibbles {
+    fn new(nibs: &[u8]) -> Self {
+        debug_assert!(nibs.len() <= MAX_NIBBLES);
+        let mut result = Self { len: nibs.len() as u8, nibs: [0; MAX_NIBBLES] };
+        result.nibs[..nibs.len()].copy_from_slice(nibs);
+        result
+    }
+
+    /// Returns the key as nibbles, or an error if it is longer than 32 bytes.
+    fn from_key(key: &[u8]) -> Result<Self, Error> {
+        if key.len() * 2 > MAX_NIBBLES {
+            return Err(Error::KeyTooLong(key.len()));
+        }
+        let mut result = Self { len: (key.len() * 2) as u8, nibs: [0; MAX_NIBBLES] };
+        for (i, byte) in key.iter().enumerate() {
+            result.nibs[2 * i] = byte >> 4;
+            result.nibs[2 * i + 1] = byte & 0xf;
+        }
+        Ok(result)
+    }
+
+    /// Decodes the hex-prefix encoded path of a leaf or extension at `depth` nibbles, and returns
+    /// whether it is the path of a leaf.
+    ///
+    /// Fails if the flag is invalid, if an extension has an empty path, or if the path leads past
+    /// [MAX_NIBBLES].
+    fn from_prefix(prefix: &[u8], depth: usize) -> Result<(Self, bool), Error> {
+        let Some((&flag, tail)) = prefix.split_first() else {
+            return Err(Error::InvalidPath(depth));
+        };
+        // the first nibble denotes whether the node is a leaf and the parity of the path, and an
+        // even path pads it with a zero nibble
+        let (is_leaf, odd) = match flag >> 4 {
+            0 => (false, false),
+            1 => (false, true),
+            2 => (true, false),
+            3 => (true, true),
+            _ => return Err(Error::InvalidPath(depth)),
+        };
+        let len = 2 * tail.len() + odd as usize;
+        if (!odd && flag & 0xf != 0) || (!is_leaf && len == 0) || depth + len > MAX_NIBBLES {
+            return Err(Error::InvalidPath(depth));
+        }
+
+        let mut result = Self { len: 0, nibs: [0; MAX_NIBBLES] };
+        if odd {
+            result.nibs[0] = flag & 0xf;
+            result.len = 1;
+        }
+        for byte in tail {
+            result.nibs[result.len as usize] = byte >> 4;
+            result.nibs[result.len as usize + 1] = byte & 0xf;
+            result.len += 2;
+        }
+        Ok((result, is_leaf))
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.nibs[..self.len as usize]
+    }
+
+    /// Returns the path with `nib` prepended.
+    fn prepend(&self, nib: u8) -> Self {
+        let mut result = Self { len: self.len + 1, nibs: [0; MAX_NIBBLES] };
+        result.nibs[0] = nib;
+        result.nibs[1..=self.len as usize].copy_from_slice(self.as_slice());
+        result
+    }
+
+    /// Returns the concatenation of both paths.
+    fn concat(&self, other: &Self) -> Self {
+        let mut result = *self;
+        result.nibs[self.len as usize..][..other.len as usize].copy_from_slice(other.as_slice());
+        result.len += other.len;
+        result
+    }
+
+    /// Appends the RLP encoding of the hex-prefix encoded path to `out`.
+    fn encode_path(&self, is_leaf: bool, out: &mut dyn alloy_rlp::BufMut) {
+        let mut nibs = self.as_slice();
+        let mut prefix = (is_leaf as u8) * 0x20;
+        if nibs.len() % 2 != 0 {
+            prefix += 0x10 + nibs[0];
+            nibs = &nibs[1..];
+        }
+        // the single flag byte of an empty path is always below 0x80 and encodes itself
+        if nibs.is_empty() {
+            out.put_u8(prefix);
+            return;
+        }
+        out.put_u8(alloy_rlp::EMPTY_STRING_CODE + 1 + (nibs.len() / 2) as u8);
+        out.put_u8(prefix);
+        for byte in nibs.chunks_exact(2) {
+            out.put_u8((byte[0] << 4) + byte[1]);
+        }
+    }
+
+    /// Returns the length of [Nibbles::encode_path].
+    fn path_length(&self) -> usize {
+        match self.len / 2 {
+            0 => 1,
+            bytes => 2 + bytes as usize,
+        }
+    }
+}
+
+/// The reference of a node: its RLP encoding if shorter than 32 bytes, or its hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeRef {
+    Bytes(u8, [u8; 31]),
+    Digest(B256),
+}
+
+impl NodeRef {
+    fn encode(&self, out: &mut dyn alloy_rlp::BufMut) {
+        match self {
+            Self::Bytes(len, bytes) => out.put_slice(&bytes[..*len as usize]),
+            Self::Digest(digest) => {
+                out.put_u8(alloy_rlp::EMPTY_STRING_CODE + 32);
+                out.put_slice(digest.as_slice());
+            }
+        }
+    }
+
+    fn length(&self) -> usize {
+        match self {
+            Self::Bytes(len, _) => *len as usize,
+            Self::Digest(_) => 1 + 32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ArenaNodeData {
+    Null,
+    Branch([Option<NodeId>; 16]),
+    Leaf(Nibbles, Vec<u8>),
+    Extension(Nibbles, NodeId),
+    Digest(B256),
+}
+
+#[derive(Debug, Clone)]
+struct ArenaNode {
+    data: ArenaNodeData,
+    cached_reference: Cell<Option<NodeRef>>,
+}
+
+/// A sparse Merkle Patricia Trie whose nodes are stored in a contiguous arena.
+///
+/// Nodes that are replaced by an update are not reclaimed, as the arena only lives for the
+/// duration of a block.
+#[derive(Debug, Clone)]
+pub struct ArenaTrie {
+    nodes: Vec<ArenaNode>,
+    root: NodeId,
+}
+
+impl Default for ArenaTrie {
+    fn default() -> Self {
+        let mut trie = Self { nodes: Vec::new(), root: NodeId(NonZeroU32::MIN) };
+        trie.root = trie.push(ArenaNodeData::Null);
+        trie
+    }
+}
+
+impl From<&MptNode> for ArenaTrie {
+    fn from(node: &MptNode) -> Self {
+        let mut trie =
+            Self { nodes: Vec::with_capacity(node.size()), root: NodeId(NonZeroU32::MIN) };
+        trie.root = trie.push_mpt_node(node);
+        trie
+    }
+}
+
+impl ArenaTrie {
+    fn push(&mut self, data: ArenaNodeData) -> NodeId {
+        self.nodes.push(ArenaNode { data, cached_reference: Cell::new(None) });
+        NodeId(NonZeroU32::new(self.nodes.len() as u32).unwrap())
+    }
+
+    fn push_mpt_node(&mut self, node: &MptNode) -> NodeId {
+        let data = match node.as_data() {
+            MptNodeData::Null => ArenaNodeData::Null,
+            MptNodeData::Branch(children) => ArenaNodeData::Branch(
+                children.each_ref().map(|child| child.as_ref().map(|c| self.push_mpt_node(c))),
+            ),
+            MptNodeData::Leaf(_, value) => {
+                ArenaNodeData::Leaf(Nibbles::new(&node.nibs()), value.clone())
+            }
+            MptNodeData::Extension(_, child) => {
+                ArenaNodeData::Extension(Nibbles::new(&node.nibs()), self.push_mpt_node(child))
+            }
+            MptNodeData::Digest(digest) => ArenaNodeData::Digest(*digest),
+        };
+        self.push(data)
+    }
+
+    /// Builds the trie with the given root hash from RLP-encoded nodes keyed by their hash.
+    ///
+    /// Hashes that are not in `nodes` remain digests. Fails if a node cannot be decoded, or if its
+    /// path leads past the 64 nibbles of a 32-byte key.
+    pub(crate) fn from_rlp_nodes(nodes: &HashMap<B256, &[u8]>, root: B256) -> Result<Self, Error> {
+        let mut trie = Self { nodes: Vec::new(), root: NodeId(NonZeroU32::MIN) };
+        trie.root = match root {
+            EMPTY_ROOT => trie.push(ArenaNodeData::Null),
+            _ => trie.push_digest(nodes, root, 0)?,
+        };
+        Ok(trie)
+    }
+
+    fn push_digest(
+        &mut self,
+        nodes: &HashMap<B256, &[u8]>,
+        digest: B256,
+        depth: usize,
+    ) -> Result<NodeId, Error> {
+        match nodes.get(&digest) {
+            Some(rlp) => self.push_rlp_node(nodes, &Rlp::new(rlp), depth),
+            None => Ok(self.push(ArenaNodeData::Digest(digest))),
+        }
+    }
+
+    /// Pushes the node at `depth` nibbles and its descendants, decoded like [MptNode]s.
+    fn push_rlp_node(
+        &mut self,
+        nodes: &HashMap<B256, &[u8]>,
+        rlp: &Rlp<'_>,
+        depth: usize,
+    ) -> Result<NodeId, Error> {
+        let data = match rlp.prototype()? {
+            Prototype::Null | Prototype::Data(0) => ArenaNodeData::Null,
+            Prototype::List(2) => {
+                let (path, is_leaf) = Nibbles::from_prefix(&rlp.val_at::<Vec<u8>>(0)?, depth)?;
+                if is_leaf {
+                    ArenaNodeData::Leaf(path, rlp.val_at(1)?)
+                } else {
+                    let child =
+                        self.push_rlp_node(nodes, &rlp.at(1)?, depth + path.len as usize)?;
+                    ArenaNodeData::Extension(path, child)
+                }
+            }
+            Prototype::List(17) => {
+                if depth >= MAX_NIBBLES {
+                    return Err(Error::InvalidPath(depth));
+                }
+                let mut children = [None; 16];
+                for (child, child_rlp) in children.iter_mut().zip(rlp.iter()) {
+                    if !matches!(child_rlp.prototype()?, Prototype::Null | Prototype::Data(0)) {
+                        *child = Some(self.push_rlp_node(nodes, &child_rlp, depth + 1)?);
+                    }
+                }
+                if !rlp.val_at::<Vec<u8>>(16)?.is_empty() {
+                    return Err(DecoderError::Custom("branch node with value").into());
+                }
+                ArenaNodeData::Branch(children)
+            }
+            Prototype::Data(32) => {
+                return self.push_digest(nodes, B256::from_slice(rlp.data()?), depth)
+            }
+            _ => return Err(DecoderError::RlpIncorrectListLen.into()),
+        };
+        Ok(self.push(data))
+    }
+
+    fn node(&self, id: NodeId) -> &ArenaNode {
+        &self.nodes[id.index()]
+    }
+
+    fn set(&mut self, id: NodeId, data: ArenaNodeData) {
+        let node = &mut self.nodes[id.index()];
+        node.data = data;
+        node.cached_reference.set(None);
+    }
+
+    /// Computes and returns the hash of the trie.
+    pub fn hash(&self) -> B256 {
+        match self.node(self.root).data {
+            ArenaNodeData::Null => EMPTY_ROOT,
+            _ => match self.reference(self.root) {
+                NodeRef::Digest(digest) => digest,
+                NodeRef::Bytes(len, bytes) => keccak(&bytes[..len as usize]).into(),
+            },
+        }
+    }
+
+    /// Returns whether the trie contains no leaves.
+    pub fn is_empty(&self) -> bool {
+        matches!(self.node(self.root).data, ArenaNodeData::Null)
+    }
+
+    /// Removes all nodes from the trie.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn reference(&self, id: NodeId) -> NodeRef {
+        let node = self.node(id);
+        if let Some(reference) = node.cached_reference.get() {
+            return reference;
+        }
+
+        let reference = match &node.data {
+            ArenaNodeData::Null => {
+                let mut bytes = [0; 31];
+                bytes[0] = alloy_rlp::EMPTY_STRING_CODE;
+                NodeRef::Bytes(1, bytes)
+            }
+            ArenaNodeData::Digest(digest) => NodeRef::Digest(*digest),
+            _ => {
+                let mut encoded = Vec::with_capacity(self.length(id));
+                self.encode(id, &mut encoded);
+                if encoded.len() < 32 {
+                    let mut bytes = [0; 31];
+                    bytes[..encoded.len()].copy_from_slice(&encoded);
+                    NodeRef::Bytes(encoded.len() as u8, bytes)
+                } else {
+                    NodeRef::Digest(keccak(encoded).into())
+                }
+            }
+        };
+        node.cached_reference.set(Some(reference));
+        reference
+    }
+
+    fn encode(&self, id: NodeId, out: &mut dyn alloy_rlp::BufMut) {
+        let payload_length = self.payload_length(id);
+        match &self.node(id).data {
+            ArenaNodeData::Null => out.put_u8(alloy_rlp::EMPTY_STRING_CODE),
+            ArenaNodeData::Branch(children) => {
+                alloy_rlp::Header { list: true, payload_length }.encode(out);
+                for child in children {
+                    match child {
+                        Some(child) => self.reference(*child).encode(out),
+                        None => out.put_u8(alloy_rlp::EMPTY_STRING_CODE),
+                    }
+                }
+                // in the MPT reference, branches have values so always add empty value
+                out.put_u8(alloy_rlp::EMPTY_STRING_CODE);
+            }
+            ArenaNodeData::Leaf(path, value) => {
+                alloy_rlp::Header { list: true, payload_length }.encode(out);
+                path.encode_path(true, out);
+                value.as_slice().encode(out);
+            }
+            ArenaNodeData::Extension(path, child) => {
+                alloy_rlp::Header { list: true, payload_length }.encode(out);
+                path.encode_path(false, out);
+                self.reference(*child).encode(out);
+            }
+            ArenaNodeData::Digest(digest) => digest.encode(out),
+        }
+    }
+
+    fn length(&self, id: NodeId) -> usize {
+        let payload_length = self.payload_length(id);
+        match &self.node(id).data {
+            ArenaNodeData::Null => 1,
+            _ => payload_length + alloy_rlp::length_of_length(payload_length),
+        }
+    }
+
+    fn payload_length(&self, id: NodeId) -> usize {
+        match &self.node(id).data {
+            ArenaNodeData::Null => 0,
+            ArenaNodeData::Branch(children) => {
+                1 + children
+                    .iter()
+                    .map(|child| child.map_or(1, |child| self.reference(child).length()))
+                    .sum::<usize>()
+            }
+            ArenaNodeData::Leaf(path, value) => path.path_length() + value.as_slice().length(),
+            ArenaNodeData::Extension(path, child) => {
+                path.path_length() + self.reference(*child).length()
+            }
+            ArenaNodeData::Digest(_) => 32,
+        }
+    }
+
+    /// Retrieves the value associated with a given key in the trie.
+    pub fn get(&self, key: &[u8]) -> Result<Option<&[u8]>, Error> {
+        let key = Nibbles::from_key(key)?;
+        let mut key_nibs = key.as_slice();
+        let mut id = self.root;
+        loop {
+            match &self.node(id).data {
+                ArenaNodeData::Null => return Ok(None),
+                ArenaNodeData::Branch(children) => {
+                    let Some((i, tail)) = key_nibs.split_first() else { return Ok(None) };
+                    let Some(child) = children[*i as usize] else { return Ok(None) };
+                    id = child;
+                    key_nibs = tail;
+                }
+                ArenaNodeData::Leaf(path, value) => {
+                    return Ok((path.as_slice() == key_nibs).then_some(value.as_slice()));
+                }
+                ArenaNodeData::Extension(path, child) => {
+                    let Some(tail) = key_nibs.strip_prefix(path.as_slice()) else {
+                        return Ok(None);
+                    };
+                    id = *child;
+                    key_nibs = tail;
+                }
+                ArenaNodeData::Digest(digest) => return Err(Error::NodeNotResolved(*digest)),
+            }
+        }
+    }
+
+    /// Retrieves the RLP-decoded value corresponding to the key.
+    pub fn get_rlp<T: alloy_rlp::Decodable>(&self, key: &[u8]) -> Result<Option<T>, Error> {
+        match self.get(key)? {
+            Some(mut bytes) => Ok(Some(T::decode(&mut bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Removes a key from the trie, returning whether it was present.
+    pub fn delete(&mut self, key: &[u8]) -> Result<bool, Error> {
+        let key = Nibbles::from_key(key)?;
+        self.delete_internal(self.root, key.as_slice())
+    }
+
+    fn delete_internal(&mut self, id: NodeId, key_nibs: &[u8]) -> Result<bool, Error> {
+        let data = match &self.node(id).data {
+            ArenaNodeData::Null => return Ok(false),
+            ArenaNodeData::Branch(children) => {
+                let mut children = *children;
+                let Some((i, tail)) = key_nibs.split_first() else {
+                    return Err(Error::ValueInBranch);
+                };
+                let Some(child) = children[*i as usize] else { return Ok(false) };
+                if !self.delete_internal(child, tail)? {
+                    return Ok(false);
+                }
+                // if the node is now empty, remove it
+                if matches!(self.node(child).data, ArenaNodeData::Null) {
+                    children[*i as usize] = None;
+                }
+
+                let mut remaining =
+                    children.iter().enumerate().filter_map(|(i, n)| Some((i, (*n)?)));
+                // there will always be at least one remaining node
+                let (index, orphan) = remaining.next().unwrap();
+                // if there is only exactly one node left, we need to convert the branch
+                if remaining.next().is_some() {
+                    ArenaNodeData::Branch(children)
+                } else {
+                    let index = index as u8;
+                    match &mut self.nodes[orphan.index()].data {
+                        // if the orphan is a leaf, prepend the corresponding nib to it
+                        ArenaNodeData::Leaf(path, value) => {
+                            ArenaNodeData::Leaf(path.prepend(index), mem::take(value))
+                        }
+                        // if the orphan is an extension, prepend the corresponding nib to it
+                        ArenaNodeData::Extension(path, child) => {
+                            ArenaNodeData::Extension(path.prepend(index), *child)
+                        }
+                        // if the orphan is a branch, convert to an extension
+                        ArenaNodeData::Branch(_) => {
+                            ArenaNodeData::Extension(Nibbles::new(&[index]), orphan)
+                        }
+                        // a digest orphan might be a leaf or an extension that has to be merged
+                        ArenaNodeData::Digest(digest) => {
+                            return Err(Error::NodeNotResolved(*digest))
+                        }
+                        ArenaNodeData::Null => unreachable!(),
+                    }
+                }
+            }
+            ArenaNodeData::Leaf(path, _) => {
+                if path.as_slice() != key_nibs {
+                    return Ok(false);
+                }
+                ArenaNodeData::Null
+            }
+            ArenaNodeData::Extension(path, child) => {
+                let (path, child) = (*path, *child);
+                let Some(tail) = key_nibs.strip_prefix(path.as_slice()) else { return Ok(false) };
+                if !self.delete_internal(child, tail)? {
+                    return Ok(false);
+                }
+
+                // an extension can only point to a branch or a digest; since its sub trie was
+                // modified, we need to make sure that this property still holds
+                match &mut self.nodes[child.index()].data {
+                    // if the child is empty, remove the extension
+                    ArenaNodeData::Null => ArenaNodeData::Null,
+                    // for a leaf, replace the extension with the extended leaf
+                    ArenaNodeData::Leaf(child_path, value) => {
+                        ArenaNodeData::Leaf(path.concat(child_path), mem::take(value))
+                    }
+                    // for an extension, replace the extension with the extended extension
+                    ArenaNodeData::Extension(child_path, node) => {
+                        ArenaNodeData::Extension(path.concat(child_path), *node)
+                    }
+                    // for a branch or digest, the extension is still correct
+                    ArenaNodeData::Branch(_) | ArenaNodeData::Digest(_) => {
+                        ArenaNodeData::Extension(path, child)
+                    }
+                }
+            }
+            ArenaNodeData::Digest(digest) => return Err(Error::NodeNotResolved(*digest)),
+        };
+
+        self.set(id, data);
+        Ok(true)
+    }
+
+    /// Inserts a key-value pair into the trie, returning whether the trie changed.
+    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) -> Result<bool, Error> {
+        if value.is_empty() {
+            panic!("value must not be empty");
+        }
+        let key = Nibbles::from_key(key)?;
+        self.insert_internal(self.root, key.as_slice(), value)
+    }
+
+    /// Inserts an RLP-encoded value into the trie.
+    pub fn insert_rlp(&mut self, key: &[u8], value: impl Encodable) -> Result<bool, Error> {
+        let key = Nibbles::from_key(key)?;
+        self.insert_internal(self.root, key.as_slice(), value.to_rlp())
+    }
+
+    fn insert_internal(
+        &mut self,
+        id: NodeId,
+        key_nibs: &[u8],
+        value: Vec<u8>,
+    ) -> Result<bool, Error> {
+        let data =
+            match &self.node(id).data {
+                ArenaNodeData::Null => ArenaNodeData::Leaf(Nibbles::new(key_nibs), value),
+                ArenaNodeData::Branch(children) => {
+                    let mut children = *children;
+                    let Some((i, tail)) = key_nibs.split_first() else {
+                        return Err(Error::ValueInBranch);
+                    };
+                    match children[*i as usize] {
+                        Some(child) => {
+                            if !self.insert_internal(child, tail, value)? {
+                                return Ok(false);
+                            }
+                        }
+                        // if the corresponding child is empty, insert a new leaf
+                        None => {
+                            children[*i as usize] =
+                                Some(self.push(ArenaNodeData::Leaf(Nibbles::new(tail), value)));
+                        }
+                    }
+                    ArenaNodeData::Branch(children)
+                }
+                ArenaNodeData::Leaf(path, old_value) => {
+                    let self_nibs = path.as_slice();
+                    let common_len = lcp(self_nibs, key_nibs);
+                    if common_len == self_nibs.len() && common_len == key_nibs.len() {
+                        // if self_nibs == key_nibs, update the value if it is different
+                        if old_value == &value {
+                            return Ok(false);
+                        }
+                        ArenaNodeData::Leaf(*path, value)
+                    } else if common_len == self_nibs.len() || common_len == key_nibs.len() {
+                        return Err(Error::ValueInBranch);
+                    } else {
+                        let (path, old_value) = (*path, old_value.clone());
+                        let self_nibs = path.as_slice();
+                        let split_point = common_len + 1;
+                        // otherwise, create a branch with two children
+                        let mut children: [Option<NodeId>; 16] = Default::default();
+                        children[self_nibs[common_len] as usize] = Some(self.push(
+                            ArenaNodeData::Leaf(Nibbles::new(&self_nibs[split_point..]), old_value),
+                        ));
+                        children[key_nibs[common_len] as usize] = Some(self.push(
+                            ArenaNodeData::Leaf(Nibbles::new(&key_nibs[split_point..]), value),
+                        ));
+                        self.branch_with_prefix(&self_nibs[..common_len], children)
+                    }
+                }
+                ArenaNodeData::Extension(path, existing_child) => {
+                    let (path, existing_child) = (*path, *existing_child);
+                    let self_nibs = path.as_slice();
+                    let common_len = lcp(self_nibs, key_nibs);
+                    if common_len == self_nibs.len() {
+                        // traverse down for update
+                        if !self.insert_internal(existing_child, &key_nibs[common_len..], value)? {
+                            return Ok(false);
+                        }
+                        ArenaNodeData::Extension(path, existing_child)
+                    } else if common_len == key_nibs.len() {
+                        return Err(Error::ValueInBranch);
+                    } else {
+                        let split_point = common_len + 1;
+                        // otherwise, create a branch with two children
+                        let mut children: [Option<NodeId>; 16] = Default::default();
+                        children[self_nibs[common_len] as usize] = if split_point < self_nibs.len()
+                        {
+                            Some(self.push(ArenaNodeData::Extension(
+                                Nibbles::new(&self_nibs[split_point..]),
+                                existing_child,
+                            )))
+                        } else {
+                            Some(existing_child)
+                        };
+                        children[key_nibs[common_len] as usize] = Some(self.push(
+                            ArenaNodeData::Leaf(Nibbles::new(&key_nibs[split_point..]), value),
+                        ));
+                        self.branch_with_prefix(&self_nibs[..common_len], children)
+                    }
+                }
+                ArenaNodeData::Digest(digest) => return Err(Error::NodeNotResolved(*digest)),
+            };
+
+        self.set(id, data);
+        Ok(true)
+    }
+
+    /// Returns a branch with the given children, behind an extension if `prefix` is not empty.
+    fn branch_with_prefix(
+        &mut self,
+        prefix: &[u8],
+        children: [Option<NodeId>; 16],
+    ) -> ArenaNodeData {
+        let branch = ArenaNodeData::Branch(children);
+        if prefix.is_empty() {
+            branch
+        } else {
+            ArenaNodeData::Extension(Nibbles::new(prefix), self.push(branch))
+        }
+    }
+}
+
+/// Returns the length of the common prefix.
+fn lcp(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// [EthereumState] backed by [ArenaTrie]s, into which the client programs can rebuild the
+/// witness to execute blocks in the zkVM.
+///
+/// It is built from the [NodeStore](crate::NodeStore) in which the host passes the witness.
+#[derive(Debug, Clone)]
+pub struct ArenaEthereumState {
+    pub state_trie: ArenaTrie,
+    pub storage_tries: HashMap<B256, ArenaTrie>,
+}
+
+impl From<&EthereumState> for ArenaEthereumState {
+    fn from(state: &EthereumState) -> Self {
+        Self {
+            state_trie: ArenaTrie::from(&state.state_trie),
+            storage_tries: state
+                .storage_tries
+                .iter()
+                .map(|(hashed_address, storage_trie)| {
+                    (*hashed_address, ArenaTrie::from(storage_trie))
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ArenaEthereumState {
+    /// Mutates state based on diffs provided in [`HashedPostState`].
+    ///
+    /// This is equivalent to [`EthereumState::update`].
+    pub fn update(&mut self, post_state: &HashedPostState) -> Result<(), StateUpdateError> {
+        for (hashed_address, account) in post_state.accounts.iter() {
+            match account {
+                Some(account) => {
+                    let storage_root = {
+                        let storage_trie = self
+                            .storage_tries
+                            .get_mut(hashed_address)
+                            .ok_or(StateUpdateError::MissingStorageTrie(*hashed_address))?;
+
+                        if let Some(state_storage) = post_state.storages.get(hashed_address) {
+                            if state_storage.wiped {
+                                storage_trie.clear();
+                            }
+
+                            for (key, value) in state_storage.storage.iter() {
+                                let result = if value.is_zero() {
+                                    storage_trie.delete(key.as_slice())
+                                } else {
+                                    storage_trie.insert_rlp(key.as_slice(), *value)
+                                };
+                                result.map_err(|err| {
+                                    StateUpdateError::from_storage_trie(*hashed_address, *key, err)
+                                })?;
+                            }
+                        }
+
+                        storage_trie.hash()
+                    };
+
+                    let state_account = TrieAccount {
+                        nonce: account.nonce,
+                        balance: account.balance,
+                        storage_root,
+                        code_hash: account.get_bytecode_hash(),
+                    };
+                    self.state_trie
+                        .insert_rlp(hashed_address.as_slice(), state_account)
+                        .map_err(|err| StateUpdateError::from_state_trie(*hashed_address, err))?;
+                }
+                None => {
+                    self.state_trie
+                        .delete(hashed_address.as_slice())
+                        .map_err(|err| StateUpdateError::from_state_trie(*hashed_address, err))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Computes the state root.
+    pub fn state_root(&self) -> B256 {
+        self.state_trie.hash()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mpt::to_nibs;
+
+    fn assert_equivalent(trie: &MptNode, arena: &ArenaTrie) {
+        assert_eq!(trie.hash(), arena.hash());
+        assert_eq!(ArenaTrie::from(trie).hash(), arena.hash());
+    }
+
+    #[test]
+    fn test_equivalence() {
+        let mut trie = MptNode::default();
+        let mut arena = ArenaTrie::default();
+        assert_equivalent(&trie, &arena);
+
+        // insert, update and delete overlapping keys
+        for i in 0..512u32 {
+            let key = keccak(i.to_be_bytes());
+            assert_eq!(trie.insert_rlp(&key, i).unwrap(), arena.insert_rlp(&key, i).unwrap());
+            assert_equivalent(&trie, &arena);
+        }
+        for i in (0..512u32).step_by(3) {
+            let key = keccak(i.to_be_bytes());
+            let value = vec![0xaa; (i % 64) as usize + 1];
+            assert_eq!(
+                trie.insert(&key, value.clone()).unwrap(),
+                arena.insert(&key, value).unwrap()
+            );
+            assert_equivalent(&trie, &arena);
+        }
+        for i in (0..600u32).step_by(2) {
+            let key = keccak(i.to_be_bytes());
+            assert_eq!(trie.delete(&key).unwrap(), arena.delete(&key).unwrap());
+            assert_equivalent(&trie, &arena);
+            assert_eq!(trie.get(&key).unwrap(), arena.get(&key).unwrap());
+        }
+        for i in 0..600u32 {
+            let key = keccak(i.to_be_bytes());
+            assert_eq!(trie.get(&key).unwrap(), arena.get(&key).unwrap());
+        }
+        for i in 0..512u32 {
+            let key = keccak(i.to_be_bytes());
+            assert_eq!(trie.delete(&key).unwrap(), arena.delete(&key).unwrap());
+            assert_equivalent(&trie, &arena);
+        }
+        assert!(arena.is_empty());
+    }
+
+    #[test]
+    fn test_short_keys() {
+        // short keys produce nodes with embedded references
+        let mut trie = MptNode::default();
+        let mut arena = ArenaTrie::default();
+        for i in 0..300u32 {
+            let key = alloy_rlp::encode(i);
+            assert_eq!(trie.insert_rlp(&key, i).unwrap(), arena.insert_rlp(&key, i).unwrap());
+            assert_equivalent(&trie, &arena);
+        }
+        for i in (0..300u32).step_by(7) {
+            let key = alloy_rlp::encode(i);
+            assert_eq!(trie.delete(&key).unwrap(), arena.delete(&key).unwrap());
+            assert_equivalent(&trie, &arena);
+        }
+
+        assert!(matches!(arena.get(&[0; 33]), Err(Error::KeyTooLong(33))));
+    }
+
+    #[test]
+    fn test_digests() {
+        let mut trie = MptNode::default();
+        for i in 0..64u32 {
+            trie.insert_rlp(&keccak(i.to_be_bytes()), i).unwrap();
+        }
+        let MptNodeData::Branch(mut children) = trie.as_data().clone() else {
+            panic!("branch expected")
+        };
+        let digest = children[0].as_ref().unwrap().hash();
+        children[0] = Some(Box::new(MptNodeData::Digest(digest).into()));
+        let sparse: MptNode = MptNodeData::Branch(children).into();
+
+        let arena = ArenaTrie::from(&sparse);
+        assert_eq!(arena.hash(), trie.hash());
+        let key =
+            (0..64u32).map(|i| keccak(i.to_be_bytes())).find(|key| to_nibs(key)[0] == 0).unwrap();
+        assert!(matches!(arena.get(&key), Err(Error::NodeNotResolved(d)) if d == digest));
+        assert!(
+            matches!(arena.clone().delete(&key), Err(Error::NodeNotResolved(d)) if d == digest)
+        );
+    }
+
+    #[test]
+    fn test_invalid_paths() {
+        fn from_root(node: MptNode) -> Result<ArenaTrie, Error> {
+            let rlp = alloy_rlp::encode(&node);
+            let nodes = HashMap::from_iter([(B256::from(keccak(&rlp)), rlp.as_slice())]);
+            ArenaTrie::from_rlp_nodes(&nodes, keccak(&rlp).into())
+        }
+        let leaf = |prefix: Vec<u8>| -> MptNode { MptNodeData::Leaf(prefix, vec![0x01]).into() };
+
+        let mut path = vec![0x20];
+        path.extend([0xab; 32]);
+        assert_eq!(from_root(leaf(path.clone())).unwrap().hash(), leaf(path).hash());
+
+        // empty path, unknown flag, odd flag of an even path, and 66 nibbles
+        for prefix in [vec![], vec![0x40, 0xab], vec![0x21, 0xab], [0x20; 34].to_vec()] {
+            assert!(matches!(from_root(leaf(prefix)), Err(Error::InvalidPath(0))));
+        }
+        // an extension with an empty path
+        let extension = MptNodeData::Extension(vec![0x00], Box::new(leaf(vec![0x20]))).into();
+        assert!(matches!(from_root(extension), Err(Error::InvalidPath(0))));
+        // 32 nibbles of extension followed by 34 nibbles of leaf
+        let mut prefix = vec![0x00];
+        prefix.extend([0xab; 16]);
+        let mut path = vec![0x20];
+        path.extend([0xab; 17]);
+        let extension = MptNodeData::Extension(prefix, Box::new(leaf(path))).into();
+        assert!(matches!(from_root(extension), Err(Error::InvalidPath(32))));
+    }
+}
@@ -0,0 +1,204 @@
+use alloy_primitives::{keccak256, map::HashMap, Address, B256, U256};
+use reth_trie::{AccountProof, HashedPostState, TrieAccount};
+
+use crate::{
+    ArenaEthereumState, Error, EthereumState, FromProofError, MptNode, NodeResolver,
+    StateUpdateError,
+};
+
+/// A commitment to the state of a chain, from which the executor reads accounts and storage
+/// slots and which it updates after executing a block.
+///
+/// [EthereumState], the hexary Merkle Patricia Trie of Ethereum, is the default implementation.
+/// Other implementations can be used to execute blocks of chains committing to their state in a
+/// different way, e.g. with a binary or a sparse Merkle tree.
+pub trait StateCommitment: Sized {
+    /// Builds the state from proofs of the touched accounts before and after a state transition.
+    fn from_transition_proofs(
+        state_root: B256,
+        parent_proofs: &HashMap<Address, AccountProof>,
+        proofs: &HashMap<Address, AccountProof>,
+    ) -> Result<Self, FromProofError>;
+
+    /// Returns the account at `address`, or `None` if it does not exist.
+    fn account(&self, address: Address) -> Result<Option<TrieAccount>, Error>;
+
+    /// Returns the value of the storage `slot` of the account at `address`.
+    ///
+    /// Fails with [Error::MissingStorageTrie] if the state lacks the storage trie of the account.
+    fn storage(&self, address: Address, slot: U256) -> Result<U256, Error>;
+
+    /// Applies the state changes.
+    fn update(&mut self, post_state: &HashedPostState) -> Result<(), StateUpdateError>;
+
+    /// Computes the state root.
+    fn state_root(&self) -> B256;
+
+    /// Applies the state changes and returns the new state root, consuming the state.
+    ///
+    /// Implementations can override this when another representation of the state is cheaper
+    /// to update once.
+    fn into_state_root(mut self, post_state: &HashedPostState) -> Result<B256, StateUpdateError> {
+        self.update(post_state)?;
+        Ok(self.state_root())
+    }
+}
+
+/// A [StateCommitment] made of [MptNode]s, whose nodes missing from the witness can be fetched
+/// by a [NodeResolver] on the host.
+pub trait MptStateCommitment: StateCommitment {
+    /// Applies the state changes, resolving the trie nodes missing from the witness with
+    /// `resolver`.
+    fn update_with_resolver<R: NodeResolver + Sync + ?Sized>(
+        &mut self,
+        post_state: &HashedPostState,
+        resolver: &R,
+    ) -> Result<(), StateUpdateError>;
+
+    /// Adds the trie nodes fetched by a [NodeResolver] to the state.
+    fn splice_nodes(&mut self, nodes: impl IntoIterator<Item = MptNode>);
+}
+
+impl StateCommitment for EthereumState {
+    fn from_transition_proofs(
+        state_root: B256,
+        parent_proofs: &HashMap<Address, AccountProof>,
+        proofs: &HashMap<Address, AccountProof>,
+    ) -> Result<Self, FromProofError> {
+        Self::from_transition_proofs(state_root, parent_proofs, proofs)
+    }
+
+    fn account(&self, address: Address) -> Result<Option<TrieAccount>, Error> {
+        self.state_trie.get_rlp(keccak256(address).as_slice())
+    }
+
+    fn storage(&self, address: Address, slot: U256) -> Result<U256, Error> {
+        let hashed_address = keccak256(address);
+        let storage_trie = self
+            .storage_tries
+            .get(&hashed_address)
+            .ok_or(Error::MissingStorageTrie(hashed_address))?;
+
+        Ok(storage_trie
+            .get_rlp::<U256>(keccak256(slot.to_be_bytes::<32>()).as_slice())?
+            .unwrap_or_default())
+    }
+
+    fn update(&mut self, post_state: &HashedPostState) -> Result<(), StateUpdateError> {
+        self.update(post_state)
+    }
+
+    fn state_root(&self) -> B256 {
+        self.state_root()
+    }
+}
+
+impl MptStateCommitment for EthereumState {
+    fn update_with_resolver<R: NodeResolver + Sync + ?Sized>(
+        &mut self,
+        post_state: &HashedPostState,
+        resolver: &R,
+    ) -> Result<(), StateUpdateError> {
+        #[cfg(feature = "rayon")]
+        {
+            self.par_update_with_resolver(post_state, resolver)
+        }
+
+        #[cfg(not(feature = "rayon"))]
+        {
+            self.update_with_resolver(post_state, resolver)
+        }
+    }
+
+    fn splice_nodes(&mut self, nodes: impl IntoIterator<Item = MptNode>) {
+        self.splice_nodes(nodes)
+    }
+}
+
+impl StateCommitment for ArenaEthereumState {
+    fn from_transition_proofs(
+        state_root: B256,
+        parent_proofs: &HashMap<Address, AccountProof>,
+        proofs: &HashMap<Address, AccountProof>,
+    ) -> Result<Self, FromProofError> {
+        EthereumState::from_transition_proofs(state_root, parent_proofs, proofs)
+            .map(|state| Self::from(&state))
+    }
+
+    fn account(&self, address: Address) -> Result<Option<TrieAccount>, Error> {
+        self.state_trie.get_rlp(keccak256(address).as_slice())
+    }
+
+    fn storage(&self, address: Address, slot: U256) -> Result<U256, Error> {
+        let hashed_address = keccak256(address);
+        let storage_trie = self
+            .storage_tries
+            .get(&hashed_address)
+            .ok_or(Error::MissingStorageTrie(hashed_address))?;
+
+        Ok(storage_trie
+            .get_rlp::<U256>(keccak256(slot.to_be_bytes::<32>()).as_slice())?
+            .unwrap_or_default())
+    }
+
+    fn update(&mut self, post_state: &HashedPostState) -> Result<(), StateUpdateError> {
+        self.update(post_state)
+    }
+
+    fn state_root(&self) -> B256 {
+        self.state_root()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reth_primitives::Account;
+    use reth_trie::HashedStorage;
+
+    use super::*;
+
+    #[test]
+    fn test_ethereum_state() {
+        let address = Address::repeat_byte(0x11);
+        let hashed_address = keccak256(address);
+        let slot = U256::from(7);
+        let hashed_slot = keccak256(slot.to_be_bytes::<32>());
+
+        let mut state =
+            EthereumState { state_trie: MptNode::default(), storage_tries: HashMap::default() };
+        state.storage_tries.insert(hashed_address, MptNode::default());
+        assert_eq!(StateCommitment::account(&state, address).unwrap(), None);
+
+        let mut post_state = HashedPostState::default();
+        post_state
+            .accounts
+            .insert(hashed_address, Some(Account { nonce: 1, ..Default::default() }));
+        post_state.storages.insert(
+            hashed_address,
+            HashedStorage::from_iter(false, [(hashed_slot, U256::from(42))]),
+        );
+        StateCommitment::update(&mut state, &post_state).unwrap();
+
+        let account = StateCommitment::account(&state, address).unwrap().unwrap();
+        assert_eq!(account.nonce, 1);
+        assert_eq!(account.storage_root, state.storage_tries[&hashed_address].hash());
+        assert_eq!(StateCommitment::storage(&state, address, slot).unwrap(), U256::from(42));
+        assert_eq!(StateCommitment::storage(&state, address, U256::ZERO).unwrap(), U256::ZERO);
+
+        let other = Address::repeat_byte(0x22);
+        assert!(matches!(
+            StateCommitment::storage(&state, other, slot),
+            Err(Error::MissingStorageTrie(hashed_address)) if hashed_address == keccak256(other)
+        ));
+        assert!(matches!(
+            StateCommitment::storage(&ArenaEthereumState::from(&state), other, slot),
+            Err(Error::MissingStorageTrie(hashed_address)) if hashed_address == keccak256(other)
+        ));
+
+        let mut post_state = HashedPostState::default();
+        post_state.accounts.insert(hashed_address, None);
+        let mut expected = state.clone();
+        expected.update(&post_state).unwrap();
+        assert_eq!(state.into_state_root(&post_state).unwrap(), expected.state_root());
+    }
+}
@@ -0,0 +1,308 @@
+use core::fmt::Write;
+
+use alloy_primitives::{
+    hex,
+    map::{HashMap, HashSet},
+    B256,
+};
+use reth_trie::HashedPostState;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    mpt::{to_nibs, MptNode, MptNodeData, MptNodeReference},
+    EthereumState,
+};
+
+/// The keys of the state and storage tries that were modified by a block, which are marked in
+/// the exported tries.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TouchedKeys {
+    /// The hashed addresses of the modified accounts.
+    pub accounts: HashSet<B256>,
+    /// The hashed slots of the modified storage, by hashed address.
+    pub storage: HashMap<B256, HashSet<B256>>,
+}
+
+impl From<&HashedPostState> for TouchedKeys {
+    fn from(post_state: &HashedPostState) -> Self {
+        Self {
+            accounts: post_state
+                .accounts
+                .keys()
+                .chain(post_state.storages.keys())
+                .copied()
+                .collect(),
+            storage: post_state
+                .storages
+                .iter()
+                .map(|(hashed_address, storage)| {
+                    (*hashed_address, storage.storage.keys().copied().collect())
+                })
+                .collect(),
+        }
+    }
+}
+
+impl MptNode {
+    /// Returns the trie as a Graphviz DOT graph, highlighting the leaves at the `touched` keys.
+    pub fn to_dot(&self, touched: &HashSet<B256>) -> String {
+        let mut writer = DotWriter::default();
+        writer.out.push_str("digraph trie {\n  node [shape=box, fontname=monospace];\n");
+        writer.write_trie(self, &touched_paths(touched));
+        writer.out.push_str("}\n");
+        writer.out
+    }
+
+    /// Returns the trie as a JSON tree, marking the leaves at the `touched` keys.
+    pub fn to_json(&self, touched: &HashSet<B256>) -> Value {
+        node_to_json(self, &mut Vec::new(), &touched_paths(touched))
+    }
+}
+
+impl EthereumState {
+    /// Returns the state trie and all storage tries as a single Graphviz DOT graph, with an edge
+    /// from every account leaf to its storage trie.
+    pub fn to_dot(&self, touched: &TouchedKeys) -> String {
+        let mut writer = DotWriter::default();
+        writer.out.push_str("digraph state {\n  node [shape=box, fontname=monospace];\n");
+
+        writer.out.push_str("  subgraph cluster_state {\n    label=\"state trie\";\n");
+        writer.write_trie(&self.state_trie, &touched_paths(&touched.accounts));
+        writer.out.push_str("  }\n");
+        let account_leaves = core::mem::take(&mut writer.leaves);
+
+        for (hashed_address, storage_trie) in sorted(&self.storage_tries) {
+            writeln!(
+                writer.out,
+                "  subgraph cluster_{hashed_address} {{\n    label=\"storage trie {hashed_address}\";"
+            )
+            .unwrap();
+            let touched =
+                touched.storage.get(hashed_address).map(touched_paths).unwrap_or_default();
+            let root = writer.write_trie(storage_trie, &touched);
+            writer.out.push_str("  }\n");
+
+            if let Some(leaf) = account_leaves.get(&to_nibs(hashed_address.as_slice())) {
+                writeln!(writer.out, "  {leaf} -> {root} [style=dotted];").unwrap();
+            }
+        }
+
+        writer.out.push_str("}\n");
+        writer.out
+    }
+
+    /// Returns the state trie and all storage tries as a JSON object.
+    pub fn to_json(&self, touched: &TouchedKeys) -> Value {
+        let storage_tries: Map<_, _> = sorted(&self.storage_tries)
+            .map(|(hashed_address, storage_trie)| {
+                let touched =
+                    touched.storage.get(hashed_address).map(touched_paths).unwrap_or_default();
+                (hashed_address.to_string(), node_to_json(storage_trie, &mut Vec::new(), &touched))
+            })
+            .collect();
+
+        json!({
+            "state_trie": node_to_json(
+                &self.state_trie,
+                &mut Vec::new(),
+                &touched_paths(&touched.accounts)
+            ),
+            "storage_tries": storage_tries,
+        })
+    }
+}
+
+/// Writes the nodes of tries as DOT statements, numbering them in the order they are visited.
+#[derive(Debug, Default)]
+struct DotWriter {
+    out: String,
+    next_id: usize,
+    /// The ids of the leaves of the last trie by their path.
+    leaves: HashMap<Vec<u8>, String>,
+}
+
+impl DotWriter {
+    /// Writes the trie and returns the id of its root.
+    fn write_trie(&mut self, root: &MptNode, touched: &HashSet<Vec<u8>>) -> String {
+        self.leaves.clear();
+        self.write_node(root, &mut Vec::new(), touched)
+    }
+
+    fn write_node(
+        &mut self,
+        node: &MptNode,
+        path: &mut Vec<u8>,
+        touched: &HashSet<Vec<u8>>,
+    ) -> String {
+        let id = format!("n{}", self.next_id);
+        self.next_id += 1;
+
+        match node.as_data() {
+            MptNodeData::Null => {
+                writeln!(self.out, "    {id} [label=\"null\"];").unwrap();
+            }
+            MptNodeData::Branch(children) => {
+                writeln!(self.out, "    {id} [label=\"branch\\n{}\"];", reference(node)).unwrap();
+                for (i, child) in children.iter().enumerate() {
+                    if let Some(child) = child {
+                        path.push(i as u8);
+                        let child_id = self.write_node(child, path, touched);
+                        path.pop();
+                        writeln!(self.out, "    {id} -> {child_id} [label=\"{i:x}\"];").unwrap();
+                    }
+                }
+            }
+            MptNodeData::Leaf(_, value) => {
+                let nibs = node.nibs();
+                path.extend(&nibs);
+                let style =
+                    if touched.contains(path) { ", style=filled, fillcolor=lightblue" } else { "" };
+                writeln!(
+                    self.out,
+                    "    {id} [label=\"leaf\\n{}\\npath {}\\nvalue {}\"{style}];",
+                    reference(node),
+                    nibbles(&nibs),
+                    hex::encode_prefixed(value)
+                )
+                .unwrap();
+                self.leaves.insert(path.clone(), id.clone());
+                path.truncate(path.len() - nibs.len());
+            }
+            MptNodeData::Extension(_, child) => {
+                let nibs = node.nibs();
+                writeln!(
+                    self.out,
+                    "    {id} [label=\"extension\\n{}\\npath {}\"];",
+                    reference(node),
+                    nibbles(&nibs)
+                )
+                .unwrap();
+                path.extend(&nibs);
+                let child_id = self.write_node(child, path, touched);
+                path.truncate(path.len() - nibs.len());
+                writeln!(self.out, "    {id} -> {child_id};").unwrap();
+            }
+            MptNodeData::Digest(digest) => {
+                writeln!(self.out, "    {id} [label=\"digest\\n{digest}\", style=dashed];")
+                    .unwrap();
+            }
+        }
+
+        id
+    }
+}
+
+fn node_to_json(node: &MptNode, path: &mut Vec<u8>, touched: &HashSet<Vec<u8>>) -> Value {
+    match node.as_data() {
+        MptNodeData::Null => json!({ "type": "null" }),
+        MptNodeData::Branch(children) => {
+            let children: Map<_, _> = children
+                .iter()
+                .enumerate()
+                .filter_map(|(i, child)| {
+                    let child = child.as_ref()?;
+                    path.push(i as u8);
+                    let child = node_to_json(child, path, touched);
+                    path.pop();
+                    Some((format!("{i:x}"), child))
+                })
+                .collect();
+            json!({ "type": "branch", "reference": reference(node), "children": children })
+        }
+        MptNodeData::Leaf(_, value) => {
+            let nibs = node.nibs();
+            path.extend(&nibs);
+            let touched = touched.contains(path);
+            path.truncate(path.len() - nibs.len());
+            json!({
+                "type": "leaf",
+                "reference": reference(node),
+                "path": nibbles(&nibs),
+                "value": hex::encode_prefixed(value),
+                "touched": touched,
+            })
+        }
+        MptNodeData::Extension(_, child) => {
+            let nibs = node.nibs();
+            path.extend(&nibs);
+            let child = node_to_json(child, path, touched);
+            path.truncate(path.len() - nibs.len());
+            json!({
+                "type": "extension",
+                "reference": reference(node),
+                "path": nibbles(&nibs),
+                "child": child,
+            })
+        }
+        MptNodeData::Digest(digest) => json!({ "type": "digest", "digest": digest }),
+    }
+}
+
+/// Formats the reference of the node, either its digest or its inline encoding.
+fn reference(node: &MptNode) -> String {
+    match node.reference() {
+        MptNodeReference::Bytes(bytes) => format!("inline {}", hex::encode_prefixed(bytes)),
+        MptNodeReference::Digest(digest) => format!("hash {digest}"),
+    }
+}
+
+/// Formats nibbles as one hex digit per nibble.
+fn nibbles(nibs: &[u8]) -> String {
+    nibs.iter().map(|nib| char::from_digit(*nib as u32, 16).unwrap()).collect()
+}
+
+fn touched_paths(keys: &HashSet<B256>) -> HashSet<Vec<u8>> {
+    keys.iter().map(|key| to_nibs(key.as_slice())).collect()
+}
+
+/// Iterates over the storage tries ordered by hashed address, for a deterministic output.
+fn sorted(storage_tries: &HashMap<B256, MptNode>) -> impl Iterator<Item = (&B256, &MptNode)> {
+    let mut storage_tries: Vec<_> = storage_tries.iter().collect();
+    storage_tries.sort_by_key(|(hashed_address, _)| *hashed_address);
+    storage_tries.into_iter()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mpt::keccak;
+
+    #[test]
+    fn test_export() {
+        let mut storage_trie = MptNode::default();
+        for i in 0u32..4 {
+            storage_trie.insert_rlp(&keccak(i.to_be_bytes()), i + 1).unwrap();
+        }
+        let hashed_address = B256::repeat_byte(0x11);
+        let mut state_trie = MptNode::default();
+        state_trie.insert_rlp(hashed_address.as_slice(), 1u32).unwrap();
+        state_trie.insert_rlp(B256::repeat_byte(0x22).as_slice(), 2u32).unwrap();
+        let state = EthereumState {
+            state_trie,
+            storage_tries: HashMap::from_iter([(hashed_address, storage_trie)]),
+        };
+
+        let touched_slot = B256::from(keccak(2u32.to_be_bytes()));
+        let touched = TouchedKeys {
+            accounts: HashSet::from_iter([hashed_address]),
+            storage: HashMap::from_iter([(hashed_address, HashSet::from_iter([touched_slot]))]),
+        };
+
+        let dot = state.to_dot(&touched);
+        assert!(dot.starts_with("digraph state {"));
+        assert_eq!(dot.matches("fillcolor=lightblue").count(), 2);
+        assert_eq!(dot.matches("style=dotted").count(), 1);
+
+        let json = state.to_json(&touched);
+        assert_eq!(json["state_trie"]["type"], "branch");
+        assert_eq!(json["state_trie"]["children"]["1"]["touched"], true);
+        assert_eq!(json["state_trie"]["children"]["2"]["touched"], false);
+        let storage_trie = json["storage_tries"][hashed_address.to_string()].to_string();
+        assert_eq!(storage_trie.matches(r#""touched":true"#).count(), 1);
+        assert_eq!(storage_trie.matches(r#""touched":false"#).count(), 3);
+
+        let digest: MptNode = MptNodeData::Digest(B256::repeat_byte(0x33)).into();
+        assert_eq!(digest.to_json(&HashSet::default())["type"], "digest");
+        assert!(digest.to_dot(&HashSet::default()).contains("style=dashed"));
+    }
+}
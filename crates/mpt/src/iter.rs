@@ -0,0 +1,431 @@
+use alloy_primitives::{map::HashMap, B256};
+use core::{
+    cmp::Ordering,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{
+    mpt::{MptNode, MptNodeData},
+    EthereumState,
+};
+
+/// An iterator over the resolved leaves of a [MptNode], in ascending key order.
+///
+/// Each item is the key of the leaf as nibbles together with its value. Subtrees that are only
+/// known by their digest are skipped, as are subtrees that cannot contain keys in the range.
+#[derive(Debug, Clone)]
+pub struct Leaves<'a> {
+    stack: Vec<(Vec<u8>, &'a MptNode)>,
+    start: Bound<Vec<u8>>,
+    end: Bound<Vec<u8>>,
+}
+
+impl<'a> Leaves<'a> {
+    fn new(root: &'a MptNode, start: Bound<Vec<u8>>, end: Bound<Vec<u8>>) -> Self {
+        Self { stack: vec![(Vec::new(), root)], start, end }
+    }
+
+    /// Returns whether the subtree at the nibble path `prefix` may contain keys in the range.
+    fn may_contain(&self, prefix: &[u8]) -> bool {
+        let after_start = match &self.start {
+            // keys with this prefix can still be larger than a start it is a prefix of
+            Bound::Included(start) | Bound::Excluded(start) => {
+                prefix >= start.as_slice() || start.starts_with(prefix)
+            }
+            Bound::Unbounded => true,
+        };
+        let before_end = match &self.end {
+            Bound::Included(end) => prefix <= end.as_slice(),
+            Bound::Excluded(end) => prefix < end.as_slice(),
+            Bound::Unbounded => true,
+        };
+        after_start && before_end
+    }
+
+    /// Returns whether the key is in the range.
+    fn contains(&self, key: &[u8]) -> bool {
+        let after_start = match &self.start {
+            Bound::Included(start) => key >= start.as_slice(),
+            Bound::Excluded(start) => key > start.as_slice(),
+            Bound::Unbounded => true,
+        };
+        let before_end = match &self.end {
+            Bound::Included(end) => key <= end.as_slice(),
+            Bound::Excluded(end) => key < end.as_slice(),
+            Bound::Unbounded => true,
+        };
+        after_start && before_end
+    }
+}
+
+impl<'a> Iterator for Leaves<'a> {
+    type Item = (Vec<u8>, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((mut path, node)) = self.stack.pop() {
+            match node.as_data() {
+                MptNodeData::Null | MptNodeData::Digest(_) => {}
+                MptNodeData::Branch(children) => {
+                    // push in reverse order, so that the smallest nibble is visited first
+                    for (i, child) in children.iter().enumerate().rev() {
+                        let Some(child) = child else { continue };
+                        let mut child_path = path.clone();
+                        child_path.push(i as u8);
+                        if self.may_contain(&child_path) {
+                            self.stack.push((child_path, child.as_ref()));
+                        }
+                    }
+                }
+                MptNodeData::Leaf(_, value) => {
+                    path.extend(node.nibs());
+                    if self.contains(&path) {
+                        return Some((path, value.as_slice()));
+                    }
+                }
+                MptNodeData::Extension(_, child) => {
+                    path.extend(node.nibs());
+                    if self.may_contain(&path) {
+                        self.stack.push((path, child.as_ref()));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// A difference between the leaves of two tries, identified by the key as nibbles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LeafDiff<'a> {
+    /// The key is only present in the new trie.
+    Inserted(Vec<u8>, &'a [u8]),
+    /// The key is only present in the old trie.
+    Removed(Vec<u8>, &'a [u8]),
+    /// The key is present in both tries with the old and the new value.
+    Changed(Vec<u8>, &'a [u8], &'a [u8]),
+}
+
+impl LeafDiff<'_> {
+    /// Returns the key of the changed leaf as nibbles.
+    pub fn key(&self) -> &[u8] {
+        match self {
+            Self::Inserted(key, _) | Self::Removed(key, _) | Self::Changed(key, _, _) => key,
+        }
+    }
+}
+
+impl MptNode {
+    /// Returns an iterator over all resolved leaves of the trie.
+    pub fn leaves(&self) -> Leaves<'_> {
+        Leaves::new(self, Bound::Unbounded, Bound::Unbounded)
+    }
+
+    /// Returns an iterator over the resolved leaves whose nibble keys are in `range`.
+    ///
+    /// Subtrees outside of the range are not visited.
+    pub fn leaves_in_range<K: AsRef<[u8]>>(&self, range: impl RangeBounds<K>) -> Leaves<'_> {
+        Leaves::new(
+            self,
+            range.start_bound().map(|start| start.as_ref().to_vec()),
+            range.end_bound().map(|end| end.as_ref().to_vec()),
+        )
+    }
+
+    /// Returns the leaves that were inserted, removed or changed in `other` compared to `self`,
+    /// in ascending key order.
+    ///
+    /// Subtrees with equal hashes are skipped. Changes inside subtrees that are only known by
+    /// their digest in either trie cannot be enumerated and are not reported.
+    pub fn diff<'a>(&'a self, other: &'a MptNode) -> Vec<LeafDiff<'a>> {
+        let mut diffs = Vec::new();
+        diff_internal(self, other, &mut Vec::new(), &mut diffs);
+        diffs
+    }
+}
+
+fn diff_internal<'a>(
+    old: &'a MptNode,
+    new: &'a MptNode,
+    prefix: &mut Vec<u8>,
+    diffs: &mut Vec<LeafDiff<'a>>,
+) {
+    // compare the hashes before descending, and give up on subtrees known only by their digest
+    if old.hash() == new.hash() || old.is_digest() || new.is_digest() {
+        return;
+    }
+
+    match (old.as_data(), new.as_data()) {
+        (MptNodeData::Branch(old_children), MptNodeData::Branch(new_children)) => {
+            for (i, (old_child, new_child)) in old_children.iter().zip(new_children).enumerate() {
+                prefix.push(i as u8);
+                match (old_child, new_child) {
+                    (Some(old_child), Some(new_child)) => {
+                        diff_internal(old_child, new_child, prefix, diffs)
+                    }
+                    (Some(old_child), None) => diffs.extend(
+                        prefixed_leaves(prefix, old_child)
+                            .map(|(key, value)| LeafDiff::Removed(key, value)),
+                    ),
+                    (None, Some(new_child)) => diffs.extend(
+                        prefixed_leaves(prefix, new_child)
+                            .map(|(key, value)| LeafDiff::Inserted(key, value)),
+                    ),
+                    (None, None) => {}
+                }
+                prefix.pop();
+            }
+        }
+        (MptNodeData::Extension(_, old_child), MptNodeData::Extension(_, new_child))
+            if old.nibs() == new.nibs() =>
+        {
+            let len = prefix.len();
+            prefix.extend(old.nibs());
+            diff_internal(old_child, new_child, prefix, diffs);
+            prefix.truncate(len);
+        }
+        // if the structure differs, compare all leaves of both subtrees, except the ones below a
+        // digest in the other subtree
+        _ => {
+            let old_digests = digest_paths(prefix, old);
+            let new_digests = digest_paths(prefix, new);
+            let mut old_leaves = prefixed_leaves(prefix, old)
+                .filter(|(key, _)| !new_digests.iter().any(|path| key.starts_with(path)))
+                .peekable();
+            let mut new_leaves = prefixed_leaves(prefix, new)
+                .filter(|(key, _)| !old_digests.iter().any(|path| key.starts_with(path)))
+                .peekable();
+            loop {
+                let diff = match (old_leaves.peek(), new_leaves.peek()) {
+                    (Some((old_key, _)), Some((new_key, _))) => match old_key.cmp(new_key) {
+                        Ordering::Less => {
+                            let (key, value) = old_leaves.next().unwrap();
+                            LeafDiff::Removed(key, value)
+                        }
+                        Ordering::Greater => {
+                            let (key, value) = new_leaves.next().unwrap();
+                            LeafDiff::Inserted(key, value)
+                        }
+                        Ordering::Equal => {
+                            let (key, old_value) = old_leaves.next().unwrap();
+                            let (_, new_value) = new_leaves.next().unwrap();
+                            if old_value == new_value {
+                                continue;
+                            }
+                            LeafDiff::Changed(key, old_value, new_value)
+                        }
+                    },
+                    (Some(_), None) => {
+                        let (key, value) = old_leaves.next().unwrap();
+                        LeafDiff::Removed(key, value)
+                    }
+                    (None, Some(_)) => {
+                        let (key, value) = new_leaves.next().unwrap();
+                        LeafDiff::Inserted(key, value)
+                    }
+                    (None, None) => break,
+                };
+                diffs.push(diff);
+            }
+        }
+    }
+}
+
+/// Returns the leaves of `node`, with keys prepended by the nibble path of the node.
+fn prefixed_leaves<'a>(
+    prefix: &[u8],
+    node: &'a MptNode,
+) -> impl Iterator<Item = (Vec<u8>, &'a [u8])> {
+    let prefix = prefix.to_vec();
+    node.leaves().map(move |(key, value)| ([prefix.as_slice(), &key].concat(), value))
+}
+
+/// Returns the nibble paths of the subtrees of `node` that are only known by their digest,
+/// prepended by the nibble path of the node.
+fn digest_paths(prefix: &[u8], node: &MptNode) -> Vec<Vec<u8>> {
+    let mut paths = Vec::new();
+    let mut stack = vec![(prefix.to_vec(), node)];
+    while let Some((mut path, node)) = stack.pop() {
+        match node.as_data() {
+            MptNodeData::Null | MptNodeData::Leaf(_, _) => {}
+            MptNodeData::Digest(_) => paths.push(path),
+            MptNodeData::Branch(children) => {
+                for (i, child) in children.iter().enumerate() {
+                    if let Some(child) = child {
+                        stack.push(([path.as_slice(), &[i as u8]].concat(), child.as_ref()));
+                    }
+                }
+            }
+            MptNodeData::Extension(_, child) => {
+                path.extend(node.nibs());
+                stack.push((path, child.as_ref()));
+            }
+        }
+    }
+    paths
+}
+
+/// The leaves that differ between two [EthereumState]s.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff<'a> {
+    /// The changes of the state trie, keyed by the hashed address.
+    pub accounts: Vec<LeafDiff<'a>>,
+    /// The changes of every storage trie that differs, keyed by the hashed address.
+    pub storages: HashMap<B256, Vec<LeafDiff<'a>>>,
+}
+
+impl EthereumState {
+    /// Returns the leaves that differ between `self` and `other`.
+    ///
+    /// Storage tries that are only part of one of the states are compared against an empty
+    /// trie.
+    pub fn diff<'a>(&'a self, other: &'a EthereumState) -> StateDiff<'a> {
+        let mut storages = HashMap::default();
+        for (hashed_address, old) in &self.storage_tries {
+            let diffs: Vec<_> = match other.storage_tries.get(hashed_address) {
+                Some(new) => old.diff(new),
+                None => old.leaves().map(|(key, value)| LeafDiff::Removed(key, value)).collect(),
+            };
+            if !diffs.is_empty() {
+                storages.insert(*hashed_address, diffs);
+            }
+        }
+        for (hashed_address, new) in &other.storage_tries {
+            if self.storage_tries.contains_key(hashed_address) {
+                continue;
+            }
+            let diffs: Vec<_> =
+                new.leaves().map(|(key, value)| LeafDiff::Inserted(key, value)).collect();
+            if !diffs.is_empty() {
+                storages.insert(*hashed_address, diffs);
+            }
+        }
+
+        StateDiff { accounts: self.state_trie.diff(&other.state_trie), storages }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mpt::{keccak, to_nibs};
+
+    fn trie(entries: impl IntoIterator<Item = (u32, u32)>) -> MptNode {
+        let mut trie = MptNode::default();
+        for (key, value) in entries {
+            trie.insert_rlp(&keccak(key.to_be_bytes()), value).unwrap();
+        }
+        trie
+    }
+
+    #[test]
+    fn test_leaves() {
+        let trie = trie((0..100).map(|i| (i, i)));
+        let mut expected: Vec<_> = (0..100u32)
+            .map(|i| (to_nibs(&keccak(i.to_be_bytes())), alloy_rlp::encode(i)))
+            .collect();
+        expected.sort();
+
+        let leaves: Vec<_> = trie.leaves().map(|(key, value)| (key, value.to_vec())).collect();
+        assert_eq!(leaves, expected);
+
+        // only the keys in the range are returned
+        let (start, end) = (&expected[10].0, &expected[20].0);
+        let leaves: Vec<_> = trie
+            .leaves_in_range(start.as_slice()..end.as_slice())
+            .map(|(key, value)| (key, value.to_vec()))
+            .collect();
+        assert_eq!(leaves, expected[10..20]);
+        let leaves: Vec<_> = trie.leaves_in_range(..=[0x8].as_slice()).collect();
+        assert!(leaves.iter().all(|(key, _)| key[0] < 0x8));
+        assert_eq!(leaves.len(), expected.iter().filter(|(key, _)| key[0] < 0x8).count());
+    }
+
+    #[test]
+    fn test_leaves_skip_digests() {
+        let trie = trie((0..100).map(|i| (i, i)));
+        let MptNodeData::Branch(mut children) = trie.as_data().clone() else {
+            panic!("branch expected")
+        };
+        let digest = children[0].as_ref().unwrap().hash();
+        children[0] = Some(Box::new(MptNodeData::Digest(digest).into()));
+        let sparse: MptNode = MptNodeData::Branch(children).into();
+
+        let expected: Vec<_> = trie.leaves().filter(|(key, _)| key[0] != 0).collect();
+        assert_eq!(sparse.leaves().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = trie((0..100).map(|i| (i, i)));
+        let mut new = old.clone();
+        new.insert_rlp(&keccak(100u32.to_be_bytes()), 100u32).unwrap();
+        new.delete(&keccak(0u32.to_be_bytes())).unwrap();
+        new.insert_rlp(&keccak(1u32.to_be_bytes()), 101u32).unwrap();
+
+        let inserted = alloy_rlp::encode(100u32);
+        let removed = alloy_rlp::encode(0u32);
+        let (changed_old, changed_new) = (alloy_rlp::encode(1u32), alloy_rlp::encode(101u32));
+        let mut expected = vec![
+            LeafDiff::Inserted(to_nibs(&keccak(100u32.to_be_bytes())), &inserted),
+            LeafDiff::Removed(to_nibs(&keccak(0u32.to_be_bytes())), &removed),
+            LeafDiff::Changed(to_nibs(&keccak(1u32.to_be_bytes())), &changed_old, &changed_new),
+        ];
+        expected.sort_by(|a, b| a.key().cmp(b.key()));
+
+        assert_eq!(old.diff(&new), expected);
+        assert!(old.diff(&old).is_empty());
+    }
+
+    #[test]
+    fn test_diff_against_digest() {
+        let old = trie((0..100).map(|i| (i, i)));
+        let MptNodeData::Branch(mut children) = old.as_data().clone() else {
+            panic!("branch expected")
+        };
+        children[0] =
+            Some(Box::new(MptNodeData::Digest(children[0].as_ref().unwrap().hash()).into()));
+        let sparse: MptNode = MptNodeData::Branch(children).into();
+
+        // a digest with the same hash as the resolved subtree is not a difference
+        assert!(old.diff(&sparse).is_empty());
+        assert!(sparse.diff(&old).is_empty());
+
+        // the changes below the digest are not reported, the other ones are
+        let (below, outside): (Vec<u32>, Vec<u32>) =
+            (0..100).partition(|i| to_nibs(&keccak(i.to_be_bytes()))[0] == 0);
+        let mut new = old.clone();
+        new.insert_rlp(&keccak(below[0].to_be_bytes()), 1000u32).unwrap();
+        new.insert_rlp(&keccak(outside[0].to_be_bytes()), 1000u32).unwrap();
+
+        let diffs = sparse.diff(&new);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].key(), to_nibs(&keccak(outside[0].to_be_bytes())));
+        assert!(matches!(diffs[0], LeafDiff::Changed(..)));
+
+        // the same holds if the digest replaces the whole trie
+        let digest: MptNode = MptNodeData::Digest(old.hash()).into();
+        assert!(digest.diff(&new).is_empty());
+        assert!(new.diff(&digest).is_empty());
+    }
+
+    #[test]
+    fn test_state_diff() {
+        let hashed_address = B256::repeat_byte(0x11);
+        let old = EthereumState {
+            state_trie: trie([(0, 0)]),
+            storage_tries: HashMap::from_iter([(hashed_address, trie([(1, 1)]))]),
+        };
+        let mut new = old.clone();
+        new.state_trie = trie([(0, 1)]);
+        new.storage_tries.insert(B256::repeat_byte(0x22), trie([(2, 2)]));
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.accounts.len(), 1);
+        assert!(matches!(diff.accounts[0], LeafDiff::Changed(..)));
+        assert_eq!(diff.storages.len(), 1);
+        assert!(matches!(
+            diff.storages[&B256::repeat_byte(0x22)].as_slice(),
+            [LeafDiff::Inserted(..)]
+        ));
+    }
+}
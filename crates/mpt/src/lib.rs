@@ -1,11 +1,32 @@
-use alloy_primitives::{map::HashMap, Address, B256};
+use alloy_primitives::{keccak256, map::HashMap, Address, B256, U256};
+use alloy_rpc_types::{EIP1186AccountProofResponse, EIP1186StorageProof};
+use reth_primitives::Account;
 use reth_trie::{AccountProof, HashedPostState, HashedStorage, TrieAccount};
 use serde::{Deserialize, Serialize};
 
 /// Module containing MPT code adapted from `zeth`.
 mod mpt;
-pub use mpt::Error;
-use mpt::{proofs_to_tries, transition_proofs_to_tries, MptNode};
+
+mod arena;
+pub use arena::{ArenaEthereumState, ArenaTrie};
+
+mod commitment;
+pub use commitment::{MptStateCommitment, StateCommitment};
+
+mod export;
+pub use export::TouchedKeys;
+
+mod iter;
+pub use iter::{LeafDiff, Leaves, StateDiff};
+
+/// Module containing the flat node list the client programs read the witness from.
+mod node_store;
+pub use node_store::NodeStore;
+
+mod stats;
+use mpt::{node_from_digest, proofs_to_tries, resolve_nodes, transition_proofs_to_tries};
+pub use mpt::{verify_proof, Error, MptNode, NodeResolver, NoopNodeResolver};
+pub use stats::{StateStats, TrieStats};
 
 /// Ethereum state trie and account storage tries.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -33,49 +54,180 @@ impl EthereumState {
     }
 
     /// Mutates state based on diffs provided in [`HashedPostState`].
-    pub fn update(&mut self, post_state: &HashedPostState) {
+    ///
+    /// Fails if the witness lacks a storage trie or a node needed to apply the diffs.
+    pub fn update(&mut self, post_state: &HashedPostState) -> Result<(), StateUpdateError> {
+        self.update_with_resolver(post_state, &NoopNodeResolver)
+    }
+
+    /// Mutates state based on diffs provided in [`HashedPostState`], fetching the nodes missing
+    /// from the witness with the given [`NodeResolver`].
+    pub fn update_with_resolver<R: NodeResolver + ?Sized>(
+        &mut self,
+        post_state: &HashedPostState,
+        resolver: &R,
+    ) -> Result<(), StateUpdateError> {
         for (hashed_address, account) in post_state.accounts.iter() {
-            let hashed_address = hashed_address.as_slice();
+            match account {
+                Some(account) => {
+                    let storage_trie = self
+                        .storage_tries
+                        .get_mut(hashed_address)
+                        .ok_or(StateUpdateError::MissingStorageTrie(*hashed_address))?;
+                    let storage_root = update_storage_trie(
+                        storage_trie,
+                        *hashed_address,
+                        post_state.storages.get(hashed_address),
+                        resolver,
+                    )?;
 
+                    self.update_account(*hashed_address, account, storage_root, resolver)?;
+                }
+                None => {
+                    self.state_trie
+                        .delete_with_resolver(hashed_address.as_slice(), resolver)
+                        .map_err(|err| StateUpdateError::from_state_trie(*hashed_address, err))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies the state changes like [`EthereumState::update_with_resolver`], but updates and
+    /// hashes the storage tries of the touched accounts in parallel before updating the state
+    /// trie.
+    ///
+    /// Errors are reported in no particular order.
+    #[cfg(feature = "rayon")]
+    pub fn par_update_with_resolver<R: NodeResolver + Sync + ?Sized>(
+        &mut self,
+        post_state: &HashedPostState,
+        resolver: &R,
+    ) -> Result<(), StateUpdateError> {
+        use rayon::prelude::*;
+
+        let missing = post_state.accounts.iter().find(|(hashed_address, account)| {
+            account.is_some() && !self.storage_tries.contains_key(*hashed_address)
+        });
+        if let Some((hashed_address, _)) = missing {
+            return Err(StateUpdateError::MissingStorageTrie(*hashed_address));
+        }
+
+        let storage_tries: Vec<_> = self
+            .storage_tries
+            .iter_mut()
+            .filter(|(hashed_address, _)| {
+                matches!(post_state.accounts.get(*hashed_address), Some(Some(_)))
+            })
+            .collect();
+        let storage_roots = storage_tries
+            .into_par_iter()
+            .map(|(hashed_address, storage_trie)| {
+                let storage = post_state.storages.get(hashed_address);
+                update_storage_trie(storage_trie, *hashed_address, storage, resolver)
+                    .map(|storage_root| (*hashed_address, storage_root))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let storage_roots: HashMap<_, _> = storage_roots.into_iter().collect();
+
+        for (hashed_address, account) in post_state.accounts.iter() {
             match account {
                 Some(account) => {
-                    let state_storage = &post_state
-                        .storages
-                        .get(hashed_address)
-                        .cloned()
-                        .unwrap_or_else(|| HashedStorage::new(false));
-                    let storage_root = {
-                        let storage_trie = self.storage_tries.get_mut(hashed_address).unwrap();
-
-                        if state_storage.wiped {
-                            storage_trie.clear();
-                        }
-
-                        for (key, value) in state_storage.storage.iter() {
-                            let key = key.as_slice();
-                            if value.is_zero() {
-                                storage_trie.delete(key).unwrap();
-                            } else {
-                                storage_trie.insert_rlp(key, *value).unwrap();
-                            }
-                        }
-
-                        storage_trie.hash()
-                    };
-
-                    let state_account = TrieAccount {
-                        nonce: account.nonce,
-                        balance: account.balance,
-                        storage_root,
-                        code_hash: account.get_bytecode_hash(),
-                    };
-                    self.state_trie.insert_rlp(hashed_address, state_account).unwrap();
+                    let storage_root = storage_roots[hashed_address];
+                    self.update_account(*hashed_address, account, storage_root, resolver)?;
                 }
                 None => {
-                    self.state_trie.delete(hashed_address).unwrap();
+                    self.state_trie
+                        .delete_with_resolver(hashed_address.as_slice(), resolver)
+                        .map_err(|err| StateUpdateError::from_state_trie(*hashed_address, err))?;
                 }
             }
         }
+
+        Ok(())
+    }
+
+    /// Inserts the account with the given storage root into the state trie.
+    fn update_account<R: NodeResolver + ?Sized>(
+        &mut self,
+        hashed_address: B256,
+        account: &Account,
+        storage_root: B256,
+        resolver: &R,
+    ) -> Result<(), StateUpdateError> {
+        let state_account = TrieAccount {
+            nonce: account.nonce,
+            balance: account.balance,
+            storage_root,
+            code_hash: account.get_bytecode_hash(),
+        };
+        self.state_trie
+            .insert_rlp_with_resolver(hashed_address.as_slice(), state_account, resolver)
+            .map_err(|err| StateUpdateError::from_state_trie(hashed_address, err))
+    }
+
+    /// Replaces the digests in the state and storage tries by the matching `nodes`.
+    ///
+    /// This is used to add the nodes fetched by a [`NodeResolver`] to the witness, so that the
+    /// same update can later be applied without a resolver.
+    pub fn splice_nodes(&mut self, nodes: impl IntoIterator<Item = MptNode>) {
+        let node_store: HashMap<_, _> =
+            nodes.into_iter().map(|node| (node.reference(), node)).collect();
+        if node_store.is_empty() {
+            return;
+        }
+
+        self.state_trie = resolve_nodes(&self.state_trie, &node_store);
+        for storage_trie in self.storage_tries.values_mut() {
+            *storage_trie = resolve_nodes(storage_trie, &node_store);
+        }
+    }
+
+    /// Returns the EIP-1186 proof of the account at `address` and of the given storage `slots`.
+    ///
+    /// Accounts that do not exist in the state are returned with all fields set to zero, which is
+    /// how `eip1186_proof_to_account_proof` recognizes them.
+    pub fn account_proof(
+        &self,
+        address: Address,
+        slots: &[B256],
+    ) -> Result<EIP1186AccountProofResponse, Error> {
+        let hashed_address = keccak256(address);
+        let account_proof = self.state_trie.prove(hashed_address.as_slice())?;
+        let account = self.state_trie.get_rlp::<TrieAccount>(hashed_address.as_slice())?;
+        let storage_hash = account.as_ref().map_or(B256::ZERO, |account| account.storage_root);
+
+        // the storage trie is only part of the witness if the account was accessed
+        let unknown_storage_trie;
+        let storage_trie = match self.storage_tries.get(&hashed_address) {
+            Some(storage_trie) => storage_trie,
+            None => {
+                unknown_storage_trie = node_from_digest(storage_hash);
+                &unknown_storage_trie
+            }
+        };
+        let storage_proof = slots
+            .iter()
+            .map(|slot| {
+                let hashed_slot = keccak256(slot);
+                Ok(EIP1186StorageProof {
+                    key: (*slot).into(),
+                    value: storage_trie.get_rlp(hashed_slot.as_slice())?.unwrap_or_default(),
+                    proof: storage_trie.prove(hashed_slot.as_slice())?,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(EIP1186AccountProofResponse {
+            address,
+            balance: account.as_ref().map_or(U256::ZERO, |account| account.balance),
+            code_hash: account.as_ref().map_or(B256::ZERO, |account| account.code_hash),
+            nonce: account.as_ref().map_or(0, |account| account.nonce),
+            storage_hash,
+            account_proof,
+            storage_proof,
+        })
     }
 
     /// Computes the state root.
@@ -84,6 +236,34 @@ impl EthereumState {
     }
 }
 
+/// Applies the storage changes of an account to its storage trie, and returns the new storage
+/// root.
+fn update_storage_trie<R: NodeResolver + ?Sized>(
+    storage_trie: &mut MptNode,
+    hashed_address: B256,
+    storage: Option<&HashedStorage>,
+    resolver: &R,
+) -> Result<B256, StateUpdateError> {
+    let Some(storage) = storage else {
+        return Ok(storage_trie.hash());
+    };
+
+    if storage.wiped {
+        storage_trie.clear();
+    }
+
+    for (key, value) in storage.storage.iter() {
+        let result = if value.is_zero() {
+            storage_trie.delete_with_resolver(key.as_slice(), resolver)
+        } else {
+            storage_trie.insert_rlp_with_resolver(key.as_slice(), *value, resolver)
+        };
+        result.map_err(|err| StateUpdateError::from_storage_trie(hashed_address, *key, err))?;
+    }
+
+    Ok(storage_trie.hash())
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum FromProofError {
     #[error("Node {} is not found by hash", .0)]
@@ -92,11 +272,67 @@ pub enum FromProofError {
     NodeHasInvalidSuccessor(usize),
     #[error("Node {} cannot have children and is invalid", .0)]
     NodeCannotHaveChildren(usize),
+    #[error("Node {} cannot be decoded: {}", .0, .1)]
+    InvalidNode(usize, Error),
+    #[error("Invalid account proof for address {}: {}", .0, .1)]
+    InvalidAccountProof(Address, Box<FromProofError>),
+    #[error("Invalid storage proof for address {}, slot {}: {}", .0, .1, .2)]
+    InvalidStorageProof(Address, B256, Box<FromProofError>),
+    #[error("Missing proof after the state transition for address {}", .0)]
+    MissingTransitionProof(Address),
     #[error("Found mismatched storage root after reconstruction \n account {}, found {}, expected {}", .0, .1, .2)]
     MismatchedStorageRoot(Address, B256, B256),
     #[error("Found mismatched staet root after reconstruction \n found {}, expected {}", .0, .1)]
     MismatchedStateRoot(B256, B256),
+    #[error("Found mismatched root when verifying proof \n found {}, expected {}", .0, .1)]
+    MismatchedProofRoot(B256, B256),
     // todo: Should decode return a decoder error?
     #[error("Error decoding proofs from bytes, {}", .0)]
     DecodingError(#[from] Error),
 }
+
+impl FromProofError {
+    /// Returns the address whose proof caused the error, if the error can be attributed to a
+    /// single account.
+    pub fn address(&self) -> Option<Address> {
+        match self {
+            Self::InvalidAccountProof(address, _) |
+            Self::InvalidStorageProof(address, _, _) |
+            Self::MissingTransitionProof(address) |
+            Self::MismatchedStorageRoot(address, _, _) => Some(*address),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StateUpdateError {
+    #[error("Missing storage trie for hashed address {}", .0)]
+    MissingStorageTrie(B256),
+    #[error("Unresolved account node {} while updating hashed address {}", .1, .0)]
+    UnresolvedAccountNode(B256, B256),
+    #[error("Unresolved storage node {} while updating hashed address {}, slot {}", .2, .0, .1)]
+    UnresolvedStorageNode(B256, B256, B256),
+    #[error("Failed to update hashed address {} in the state trie: {}", .0, .1)]
+    StateTrie(B256, Error),
+    #[error("Failed to update hashed address {}, slot {} in the storage trie: {}", .0, .1, .2)]
+    StorageTrie(B256, B256, Error),
+}
+
+impl StateUpdateError {
+    fn from_storage_trie(hashed_address: B256, slot: B256, err: Error) -> Self {
+        match err {
+            Error::NodeNotResolved(digest) => {
+                Self::UnresolvedStorageNode(hashed_address, slot, digest)
+            }
+            err => Self::StorageTrie(hashed_address, slot, err),
+        }
+    }
+
+    fn from_state_trie(hashed_address: B256, err: Error) -> Self {
+        match err {
+            Error::NodeNotResolved(digest) => Self::UnresolvedAccountNode(hashed_address, digest),
+            err => Self::StateTrie(hashed_address, err),
+        }
+    }
+}
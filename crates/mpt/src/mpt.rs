@@ -20,13 +20,13 @@
 #![allow(dead_code)]
 
 use alloc::boxed::Box;
-use alloy_primitives::{b256, map::HashMap, B256};
+use alloy_primitives::{b256, map::HashMap, Bytes, B256};
 use alloy_rlp::Encodable;
 use core::{
-    cell::RefCell,
     cmp,
     fmt::{Debug, Write},
     iter, mem,
+    ops::Deref,
 };
 use reth_trie::AccountProof;
 
@@ -98,7 +98,68 @@ pub struct MptNode {
     /// Cache for a previously computed reference of this node. This is skipped during
     /// serialization.
     #[serde(skip)]
-    cached_reference: RefCell<Option<MptNodeReference>>,
+    cached_reference: ReferenceCache,
+}
+
+/// Cache for the reference of an [MptNode].
+///
+/// The cache is a `RefCell` by default, which keeps the zkVM free of synchronization. With the
+/// `rayon` feature, it is a `OnceLock`, which makes the tries `Sync` so that they can be hashed
+/// from several threads. The cache is derived from the node data, so it is ignored in
+/// comparisons.
+#[derive(Clone, Debug, Default)]
+struct ReferenceCache(
+    #[cfg(not(feature = "rayon"))] core::cell::RefCell<Option<MptNodeReference>>,
+    #[cfg(feature = "rayon")] std::sync::OnceLock<MptNodeReference>,
+);
+
+impl ReferenceCache {
+    /// Returns the cached reference, computing it with `f` if it is not cached yet.
+    #[cfg(not(feature = "rayon"))]
+    fn get_or_init(
+        &self,
+        f: impl FnOnce() -> MptNodeReference,
+    ) -> impl Deref<Target = MptNodeReference> + '_ {
+        if self.0.borrow().is_none() {
+            let reference = f();
+            *self.0.borrow_mut() = Some(reference);
+        }
+        core::cell::Ref::map(self.0.borrow(), |reference| reference.as_ref().unwrap())
+    }
+
+    /// Returns the cached reference, computing it with `f` if it is not cached yet.
+    #[cfg(feature = "rayon")]
+    fn get_or_init(
+        &self,
+        f: impl FnOnce() -> MptNodeReference,
+    ) -> impl Deref<Target = MptNodeReference> + '_ {
+        self.0.get_or_init(f)
+    }
+
+    /// Clears the cached reference.
+    fn clear(&mut self) {
+        self.0.take();
+    }
+}
+
+impl PartialEq for ReferenceCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for ReferenceCache {}
+
+impl PartialOrd for ReferenceCache {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ReferenceCache {
+    fn cmp(&self, _other: &Self) -> cmp::Ordering {
+        cmp::Ordering::Equal
+    }
 }
 
 /// Represents custom error types for the sparse Merkle Patricia Trie (MPT).
@@ -115,6 +176,21 @@ pub enum Error {
     /// Occurs when a value is unexpectedly found in a branch node.
     #[error("branch node with value")]
     ValueInBranch,
+    /// Occurs when a key does not fit into a trie with fixed-size keys. The associated value is
+    /// the length of the key in bytes.
+    #[error("key of {0} bytes exceeds 32 bytes")]
+    KeyTooLong(usize),
+    /// Occurs when the witness lacks the storage trie of an account. The associated value is the
+    /// hashed address of the account.
+    #[error("missing storage trie for hashed address {0}")]
+    MissingStorageTrie(B256),
+    /// Occurs when the path of a decoded node is malformed or leads past the 64 nibbles of a
+    /// 32-byte key. The associated value is the depth of the node in nibbles.
+    #[error("invalid node path at depth {0}")]
+    InvalidPath(usize),
+    /// Occurs when a [NodeResolver] returns a node that does not match the requested digest.
+    #[error("resolved node does not match its digest: {0:#}")]
+    InvalidResolvedNode(B256),
     /// Represents errors related to the RLP encoding and decoding using the `alloy_rlp`
     /// library.
     #[error("RLP error")]
@@ -125,6 +201,27 @@ pub enum Error {
     LegacyRlp(#[from] DecoderError),
 }
 
+/// Provides the nodes of a sparse Merkle Patricia Trie (MPT) that are only known by their
+/// digest.
+///
+/// Trie operations consult the resolver whenever they reach a [MptNodeData::Digest] that must
+/// be expanded, e.g. the remaining sibling of a branch that collapses after a deletion.
+pub trait NodeResolver {
+    /// Returns the node whose hash is `digest`, or `None` if it is unknown.
+    fn resolve(&self, digest: B256) -> Option<MptNode>;
+}
+
+/// A [NodeResolver] that never resolves any node.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopNodeResolver;
+
+impl NodeResolver for NoopNodeResolver {
+    #[inline]
+    fn resolve(&self, _digest: B256) -> Option<MptNode> {
+        None
+    }
+}
+
 /// Represents the various types of data that can be stored within a node in the sparse
 /// Merkle Patricia Trie (MPT).
 ///
@@ -171,7 +268,7 @@ pub enum MptNodeReference {
 /// `cached_reference` field to `None`.
 impl From<MptNodeData> for MptNode {
     fn from(value: MptNodeData) -> Self {
-        Self { data: value, cached_reference: RefCell::new(None) }
+        Self { data: value, cached_reference: ReferenceCache::default() }
     }
 }
 
@@ -246,7 +343,9 @@ impl Decodable for MptNode {
             Prototype::Null | Prototype::Data(0) => Ok(MptNodeData::Null.into()),
             Prototype::List(2) => {
                 let path: Vec<u8> = rlp.val_at(0)?;
-                let prefix = path[0];
+                let Some(&prefix) = path.first() else {
+                    return Err(DecoderError::Custom("node with empty path"));
+                };
                 if (prefix & (2 << 4)) == 0 {
                     let node: MptNode = Decodable::decode(&rlp.at(1)?)?;
                     Ok(MptNodeData::Extension(path, Box::new(node)).into())
@@ -320,7 +419,7 @@ impl MptNode {
     /// storage or transmission purposes.
     #[inline]
     pub fn reference(&self) -> MptNodeReference {
-        self.cached_reference.borrow_mut().get_or_insert_with(|| self.calc_reference()).clone()
+        self.cached_reference.get_or_init(|| self.calc_reference()).clone()
     }
 
     /// Computes and returns the 256-bit hash of the node.
@@ -330,11 +429,7 @@ impl MptNode {
     pub fn hash(&self) -> B256 {
         match self.data {
             MptNodeData::Null => EMPTY_ROOT,
-            _ => match self
-                .cached_reference
-                .borrow_mut()
-                .get_or_insert_with(|| self.calc_reference())
-            {
+            _ => match &*self.cached_reference.get_or_init(|| self.calc_reference()) {
                 MptNodeReference::Digest(digest) => *digest,
                 MptNodeReference::Bytes(bytes) => keccak(bytes).into(),
             },
@@ -343,7 +438,7 @@ impl MptNode {
 
     /// Encodes the [MptNodeReference] of this node into the `out` buffer.
     fn reference_encode(&self, out: &mut dyn alloy_rlp::BufMut) {
-        match self.cached_reference.borrow_mut().get_or_insert_with(|| self.calc_reference()) {
+        match &*self.cached_reference.get_or_init(|| self.calc_reference()) {
             // if the reference is an RLP-encoded byte slice, copy it directly
             MptNodeReference::Bytes(bytes) => out.put_slice(bytes),
             // if the reference is a digest, RLP-encode it with its fixed known length
@@ -356,7 +451,7 @@ impl MptNode {
 
     /// Returns the length of the encoded [MptNodeReference] of this node.
     fn reference_length(&self) -> usize {
-        match self.cached_reference.borrow_mut().get_or_insert_with(|| self.calc_reference()) {
+        match &*self.cached_reference.get_or_init(|| self.calc_reference()) {
             MptNodeReference::Bytes(bytes) => bytes.len(),
             MptNodeReference::Digest(_) => 1 + 32,
         }
@@ -428,6 +523,51 @@ impl MptNode {
         }
     }
 
+    /// Returns the EIP-1186 proof of the given key.
+    ///
+    /// The proof consists of the RLP-encoded root node followed by every hash-referenced node
+    /// on the path of the key. If the key is not present in the trie, the nodes of the longest
+    /// existing prefix are returned, proving its exclusion.
+    pub fn prove(&self, key: &[u8]) -> Result<Vec<Bytes>, Error> {
+        let mut proof = Vec::new();
+        if !self.is_empty() {
+            self.prove_internal(&to_nibs(key), true, &mut proof)?;
+        }
+        Ok(proof)
+    }
+
+    fn prove_internal(
+        &self,
+        key_nibs: &[u8],
+        is_root: bool,
+        proof: &mut Vec<Bytes>,
+    ) -> Result<(), Error> {
+        if let MptNodeData::Digest(digest) = &self.data {
+            return Err(Error::NodeNotResolved(*digest));
+        }
+        // nodes with short encodings are embedded in their parent
+        if is_root || matches!(self.reference(), MptNodeReference::Digest(_)) {
+            proof.push(alloy_rlp::encode(self).into());
+        }
+
+        match &self.data {
+            MptNodeData::Branch(children) => {
+                if let Some((i, tail)) = key_nibs.split_first() {
+                    if let Some(node) = &children[*i as usize] {
+                        node.prove_internal(tail, false, proof)?;
+                    }
+                }
+            }
+            MptNodeData::Extension(prefix, node) => {
+                if let Some(tail) = key_nibs.strip_prefix(prefix_nibs(prefix).as_slice()) {
+                    node.prove_internal(tail, false, proof)?;
+                }
+            }
+            MptNodeData::Null | MptNodeData::Leaf(_, _) | MptNodeData::Digest(_) => {}
+        }
+        Ok(())
+    }
+
     fn get_internal(&self, key_nibs: &[u8]) -> Result<Option<&[u8]>, Error> {
         match &self.data {
             MptNodeData::Null => Ok(None),
@@ -465,10 +605,28 @@ impl MptNode {
     /// present, it returns `true`. Otherwise, it returns `false`.
     #[inline]
     pub fn delete(&mut self, key: &[u8]) -> Result<bool, Error> {
-        self.delete_internal(&to_nibs(key))
+        self.delete_internal(&to_nibs(key), &NoopNodeResolver)
+    }
+
+    /// Removes a key from the trie, expanding digests with the given [NodeResolver].
+    ///
+    /// This behaves like [MptNode::delete], but a digest on the path of the key or a digest
+    /// left as the only child of a branch is replaced by the resolved node.
+    #[inline]
+    pub fn delete_with_resolver<R: NodeResolver + ?Sized>(
+        &mut self,
+        key: &[u8],
+        resolver: &R,
+    ) -> Result<bool, Error> {
+        self.delete_internal(&to_nibs(key), resolver)
     }
 
-    fn delete_internal(&mut self, key_nibs: &[u8]) -> Result<bool, Error> {
+    fn delete_internal<R: NodeResolver + ?Sized>(
+        &mut self,
+        key_nibs: &[u8],
+        resolver: &R,
+    ) -> Result<bool, Error> {
+        self.resolve_digest(resolver)?;
         match &mut self.data {
             MptNodeData::Null => return Ok(false),
             MptNodeData::Branch(children) => {
@@ -476,7 +634,7 @@ impl MptNode {
                     let child = &mut children[*i as usize];
                     match child {
                         Some(node) => {
-                            if !node.delete_internal(tail)? {
+                            if !node.delete_internal(tail, resolver)? {
                                 return Ok(false);
                             }
                             // if the node is now empty, remove it
@@ -496,6 +654,9 @@ impl MptNode {
                 // if there is only exactly one node left, we need to convert the branch
                 if remaining.next().is_none() {
                     let mut orphan = node.take().unwrap();
+                    // a digest orphan might be a leaf or an extension that has to be merged, so
+                    // it must be resolved
+                    orphan.resolve_digest(resolver)?;
                     match &mut orphan.data {
                         // if the orphan is a leaf, prepend the corresponding nib to it
                         MptNodeData::Leaf(prefix, orphan_value) => {
@@ -515,14 +676,14 @@ impl MptNode {
                                 mem::take(orphan_child),
                             );
                         }
-                        // if the orphan is a branch or digest, convert to an extension
-                        MptNodeData::Branch(_) | MptNodeData::Digest(_) => {
+                        // if the orphan is a branch, convert to an extension
+                        MptNodeData::Branch(_) => {
                             self.data = MptNodeData::Extension(
                                 to_encoded_path(&[index as u8], false),
                                 orphan,
                             );
                         }
-                        MptNodeData::Null => unreachable!(),
+                        MptNodeData::Null | MptNodeData::Digest(_) => unreachable!(),
                     }
                 }
             }
@@ -535,7 +696,7 @@ impl MptNode {
             MptNodeData::Extension(prefix, child) => {
                 let mut self_nibs = prefix_nibs(prefix);
                 if let Some(tail) = key_nibs.strip_prefix(self_nibs.as_slice()) {
-                    if !child.delete_internal(tail)? {
+                    if !child.delete_internal(tail, resolver)? {
                         return Ok(false);
                     }
                 } else {
@@ -584,7 +745,7 @@ impl MptNode {
         if value.is_empty() {
             panic!("value must not be empty");
         }
-        self.insert_internal(&to_nibs(key), value)
+        self.insert_internal(&to_nibs(key), value, &NoopNodeResolver)
     }
 
     /// Inserts an RLP-encoded value into the trie.
@@ -592,10 +753,28 @@ impl MptNode {
     /// This method inserts a value that's been encoded using RLP into the trie.
     #[inline]
     pub fn insert_rlp(&mut self, key: &[u8], value: impl Encodable) -> Result<bool, Error> {
-        self.insert_internal(&to_nibs(key), value.to_rlp())
+        self.insert_internal(&to_nibs(key), value.to_rlp(), &NoopNodeResolver)
+    }
+
+    /// Inserts an RLP-encoded value into the trie, expanding digests with the given
+    /// [NodeResolver].
+    #[inline]
+    pub fn insert_rlp_with_resolver<R: NodeResolver + ?Sized>(
+        &mut self,
+        key: &[u8],
+        value: impl Encodable,
+        resolver: &R,
+    ) -> Result<bool, Error> {
+        self.insert_internal(&to_nibs(key), value.to_rlp(), resolver)
     }
 
-    fn insert_internal(&mut self, key_nibs: &[u8], value: Vec<u8>) -> Result<bool, Error> {
+    fn insert_internal<R: NodeResolver + ?Sized>(
+        &mut self,
+        key_nibs: &[u8],
+        value: Vec<u8>,
+        resolver: &R,
+    ) -> Result<bool, Error> {
+        self.resolve_digest(resolver)?;
         match &mut self.data {
             MptNodeData::Null => {
                 self.data = MptNodeData::Leaf(to_encoded_path(key_nibs, true), value);
@@ -605,7 +784,7 @@ impl MptNode {
                     let child = &mut children[*i as usize];
                     match child {
                         Some(node) => {
-                            if !node.insert_internal(tail, value)? {
+                            if !node.insert_internal(tail, value, resolver)? {
                                 return Ok(false);
                             }
                         }
@@ -665,7 +844,7 @@ impl MptNode {
                 let common_len = lcp(&self_nibs, key_nibs);
                 if common_len == self_nibs.len() {
                     // traverse down for update
-                    if !existing_child.insert_internal(&key_nibs[common_len..], value)? {
+                    if !existing_child.insert_internal(&key_nibs[common_len..], value, resolver)? {
                         return Ok(false);
                     }
                 } else if common_len == key_nibs.len() {
@@ -710,8 +889,22 @@ impl MptNode {
         Ok(true)
     }
 
+    /// Replaces a digest node with the node returned by the [NodeResolver].
+    ///
+    /// The resolved node must hash to the digest, so the reference of the node is unchanged.
+    fn resolve_digest<R: NodeResolver + ?Sized>(&mut self, resolver: &R) -> Result<(), Error> {
+        if let MptNodeData::Digest(digest) = self.data {
+            let node = resolver.resolve(digest).ok_or(Error::NodeNotResolved(digest))?;
+            if node.hash() != digest {
+                return Err(Error::InvalidResolvedNode(digest));
+            }
+            *self = node;
+        }
+        Ok(())
+    }
+
     fn invalidate_ref_cache(&mut self) {
-        self.cached_reference.borrow_mut().take();
+        self.cached_reference.clear();
     }
 
     /// Returns the number of traversable nodes in the trie.
@@ -846,8 +1039,39 @@ fn prefix_nibs(prefix: &[u8]) -> Vec<u8> {
 }
 
 /// Parses proof bytes into a vector of MPT nodes.
-pub fn parse_proof(proof: &[impl AsRef<[u8]>]) -> Result<Vec<MptNode>, Error> {
-    proof.iter().map(MptNode::decode).collect()
+pub fn parse_proof(proof: &[impl AsRef<[u8]>]) -> Result<Vec<MptNode>, FromProofError> {
+    proof
+        .iter()
+        .enumerate()
+        .map(|(i, node)| MptNode::decode(node).map_err(|err| FromProofError::InvalidNode(i, err)))
+        .collect()
+}
+
+/// Parses proof bytes into a vector of MPT nodes and checks that they form a valid path.
+fn parse_and_validate_proof(proof: &[impl AsRef<[u8]>]) -> Result<Vec<MptNode>, FromProofError> {
+    let proof_nodes = parse_proof(proof)?;
+    mpt_from_proof(&proof_nodes)?;
+
+    Ok(proof_nodes)
+}
+
+/// Parses the account proof of `address`, attaching the address to any error.
+fn parse_account_proof(
+    address: &Address,
+    proof: &[impl AsRef<[u8]>],
+) -> Result<Vec<MptNode>, FromProofError> {
+    parse_and_validate_proof(proof)
+        .map_err(|err| FromProofError::InvalidAccountProof(*address, Box::new(err)))
+}
+
+/// Parses the proof of `slot` in the storage of `address`, attaching both to any error.
+fn parse_storage_proof(
+    address: &Address,
+    slot: B256,
+    proof: &[impl AsRef<[u8]>],
+) -> Result<Vec<MptNode>, FromProofError> {
+    parse_and_validate_proof(proof)
+        .map_err(|err| FromProofError::InvalidStorageProof(*address, slot, Box::new(err)))
 }
 
 /// Creates a Merkle Patricia trie from an EIP-1186 proof.
@@ -897,13 +1121,33 @@ pub fn mpt_from_proof(proof_nodes: &[MptNode]) -> Result<MptNode, FromProofError
 
 /// Verifies that the given proof is a valid proof of exclusion for the given key.
 pub fn is_not_included(key: &[u8], proof_nodes: &[MptNode]) -> Result<bool, FromProofError> {
-    let proof_trie = mpt_from_proof(proof_nodes).unwrap();
+    let proof_trie = mpt_from_proof(proof_nodes)?;
     // for valid proofs, the get must not fail
-    let value = proof_trie.get(key).unwrap();
+    let value = proof_trie.get(key)?;
 
     Ok(value.is_none())
 }
 
+/// Verifies an EIP-1186 proof of `key` against `root`.
+///
+/// Returns the value of the key, or `None` if the proof shows that the key is not included.
+pub fn verify_proof(
+    root: B256,
+    key: &[u8],
+    proof: &[impl AsRef<[u8]>],
+) -> Result<Option<Vec<u8>>, FromProofError> {
+    let proof_nodes = parse_proof(proof)?;
+    let proof_trie = mpt_from_proof(&proof_nodes)?;
+    let proof_root = proof_trie.hash();
+    if proof_root != root {
+        return Err(FromProofError::MismatchedProofRoot(proof_root, root));
+    }
+    // for valid proofs, the get must not fail
+    let value = proof_trie.get(key)?;
+
+    Ok(value.map(|value| value.to_vec()))
+}
+
 /// Creates a new MPT trie where all the digests contained in `node_store` are resolved.
 pub fn resolve_nodes(root: &MptNode, node_store: &HashMap<MptNodeReference, MptNode>) -> MptNode {
     let trie = match root.as_data() {
@@ -977,8 +1221,7 @@ pub fn proofs_to_tries(
     let mut state_nodes = HashMap::with_hasher(Default::default());
     let mut state_root_node = MptNode::default();
     for (address, proof) in proofs {
-        let proof_nodes = parse_proof(&proof.proof).unwrap();
-        mpt_from_proof(&proof_nodes).unwrap();
+        let proof_nodes = parse_account_proof(address, &proof.proof)?;
 
         // the first node in the proof is the root
         if let Some(node) = proof_nodes.first() {
@@ -1000,8 +1243,8 @@ pub fn proofs_to_tries(
         let mut storage_nodes = HashMap::with_hasher(Default::default());
         let mut storage_root_node = MptNode::default();
         for storage_proof in &proof.storage_proofs {
-            let proof_nodes = parse_proof(&storage_proof.proof).unwrap();
-            mpt_from_proof(&proof_nodes).unwrap();
+            let proof_nodes =
+                parse_storage_proof(address, storage_proof.key, &storage_proof.proof)?;
 
             // the first node in the proof is the root
             if let Some(node) = proof_nodes.first() {
@@ -1054,8 +1297,7 @@ pub fn transition_proofs_to_tries(
     let mut state_nodes = HashMap::with_hasher(Default::default());
     let mut state_root_node = MptNode::default();
     for (address, proof) in parent_proofs {
-        let proof_nodes = parse_proof(&proof.proof).unwrap();
-        mpt_from_proof(&proof_nodes).unwrap();
+        let proof_nodes = parse_account_proof(address, &proof.proof)?;
 
         // the first node in the proof is the root
         if let Some(node) = proof_nodes.first() {
@@ -1066,10 +1308,12 @@ pub fn transition_proofs_to_tries(
             state_nodes.insert(node.reference(), node);
         });
 
-        let fini_proofs = proofs.get(address).unwrap();
+        let fini_proofs =
+            proofs.get(address).ok_or(FromProofError::MissingTransitionProof(*address))?;
 
         // assure that addresses can be deleted from the state trie
-        add_orphaned_leafs(address, &fini_proofs.proof, &mut state_nodes)?;
+        add_orphaned_leafs(address, &fini_proofs.proof, &mut state_nodes)
+            .map_err(|err| FromProofError::InvalidAccountProof(*address, Box::new(err)))?;
 
         // if no slots are provided, return the trie only consisting of the storage root
         let storage_root = proof.storage_root;
@@ -1082,8 +1326,8 @@ pub fn transition_proofs_to_tries(
         let mut storage_nodes = HashMap::with_hasher(Default::default());
         let mut storage_root_node = MptNode::default();
         for storage_proof in &proof.storage_proofs {
-            let proof_nodes = parse_proof(&storage_proof.proof).unwrap();
-            mpt_from_proof(&proof_nodes).unwrap();
+            let proof_nodes =
+                parse_storage_proof(address, storage_proof.key, &storage_proof.proof)?;
 
             // the first node in the proof is the root
             if let Some(node) = proof_nodes.first() {
@@ -1097,7 +1341,10 @@ pub fn transition_proofs_to_tries(
 
         // assure that slots can be deleted from the storage trie
         for storage_proof in &fini_proofs.storage_proofs {
-            add_orphaned_leafs(storage_proof.key.0, &storage_proof.proof, &mut storage_nodes)?;
+            add_orphaned_leafs(storage_proof.key.0, &storage_proof.proof, &mut storage_nodes)
+                .map_err(|err| {
+                    FromProofError::InvalidStorageProof(*address, storage_proof.key, Box::new(err))
+                })?;
         }
         // create the storage trie, from all the relevant nodes
         let storage_trie = resolve_nodes(&storage_root_node, &storage_nodes);
@@ -1143,7 +1390,7 @@ fn add_orphaned_leafs(
 }
 
 /// Creates a new MPT node from a digest.
-fn node_from_digest(digest: B256) -> MptNode {
+pub fn node_from_digest(digest: B256) -> MptNode {
     match digest {
         EMPTY_ROOT | B256::ZERO => MptNode::default(),
         _ => MptNodeData::Digest(digest).into(),
@@ -1284,6 +1531,234 @@ mod tests {
         trie.get(b"a0").unwrap_err();
     }
 
+    #[test]
+    pub fn test_empty_path_node() {
+        // a two-item list with an empty path is neither a leaf nor an extension
+        MptNode::decode(hex!("c28080")).unwrap_err();
+    }
+
+    #[test]
+    pub fn test_invalid_account_proof() {
+        let address = Address::repeat_byte(0x11);
+        let mut proof = AccountProof::new(address);
+        // a list with three items is not a valid node
+        proof.proof = vec![alloy_primitives::Bytes::from_static(&hex!("c3010203"))];
+        let proofs = HashMap::from_iter([(address, proof)]);
+
+        let err = proofs_to_tries(B256::repeat_byte(0x22), &proofs).unwrap_err();
+        assert_eq!(err.address(), Some(address));
+        let FromProofError::InvalidAccountProof(_, err) = err else {
+            panic!("invalid account proof expected")
+        };
+        assert!(matches!(*err, FromProofError::InvalidNode(0, _)));
+    }
+
+    #[test]
+    pub fn test_missing_transition_proof() {
+        let address = Address::repeat_byte(0x11);
+        let parent_proofs = HashMap::from_iter([(address, AccountProof::new(address))]);
+
+        let err = transition_proofs_to_tries(
+            B256::repeat_byte(0x22),
+            &parent_proofs,
+            &HashMap::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, FromProofError::MissingTransitionProof(a) if a == address));
+    }
+
+    #[test]
+    pub fn test_update_unresolved_nodes() {
+        use crate::StateUpdateError;
+        use reth_primitives::Account;
+        use reth_trie::HashedPostState;
+
+        let state_root = B256::repeat_byte(0x11);
+        let hashed_address = B256::repeat_byte(0x22);
+        let mut state = EthereumState {
+            state_trie: node_from_digest(state_root),
+            storage_tries: HashMap::default(),
+        };
+
+        // the storage trie of an updated account must be part of the witness
+        let mut post_state = HashedPostState::default();
+        post_state.accounts.insert(hashed_address, Some(Account::default()));
+        let err = state.clone().update(&post_state).unwrap_err();
+        assert!(matches!(err, StateUpdateError::MissingStorageTrie(a) if a == hashed_address));
+
+        // deleting from an unresolved state trie reports the missing digest
+        let mut post_state = HashedPostState::default();
+        post_state.accounts.insert(hashed_address, None);
+        let err = state.update(&post_state).unwrap_err();
+        assert!(matches!(
+            err,
+            StateUpdateError::UnresolvedAccountNode(a, d) if a == hashed_address && d == state_root
+        ));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    pub fn test_par_update() {
+        use alloy_primitives::U256;
+        use reth_primitives::Account;
+        use reth_trie::{HashedPostState, HashedStorage};
+
+        let mut state =
+            EthereumState { state_trie: MptNode::default(), storage_tries: HashMap::default() };
+        let mut post_state = HashedPostState::default();
+        for i in 0u64..20 {
+            let hashed_address = B256::from(keccak(i.to_be_bytes()));
+            let account = Account { nonce: i, ..Default::default() };
+            let storage =
+                (0u64..i).map(|j| (B256::from(keccak(j.to_be_bytes())), U256::from(i * j)));
+            state.storage_tries.insert(hashed_address, MptNode::default());
+            post_state.accounts.insert(hashed_address, Some(account));
+            post_state.storages.insert(hashed_address, HashedStorage::from_iter(false, storage));
+        }
+
+        let mut expected = state.clone();
+        expected.update(&post_state).unwrap();
+        state.par_update_with_resolver(&post_state, &NoopNodeResolver).unwrap();
+        assert_eq!(state.state_root(), expected.state_root());
+        assert_eq!(state, expected);
+    }
+
+    #[test]
+    pub fn test_delete_with_resolver() {
+        struct TestResolver(HashMap<B256, MptNode>);
+
+        impl NodeResolver for TestResolver {
+            fn resolve(&self, digest: B256) -> Option<MptNode> {
+                self.0.get(&digest).cloned()
+            }
+        }
+
+        let key = B256::repeat_byte(0x11);
+        let sibling_key = B256::repeat_byte(0x22);
+        let value = vec![0xaa; 40];
+
+        let mut expected = MptNode::default();
+        expected.insert(sibling_key.as_slice(), value.clone()).unwrap();
+        let mut trie = expected.clone();
+        trie.insert(key.as_slice(), value).unwrap();
+
+        // replace the sibling leaf in the root branch by its digest
+        let MptNodeData::Branch(mut children) = trie.as_data().clone() else {
+            panic!("branch expected")
+        };
+        let sibling = children[2].take().unwrap();
+        children[2] = Some(Box::new(node_from_digest(sibling.hash())));
+        let sparse: MptNode = MptNodeData::Branch(children).into();
+        assert_eq!(sparse.hash(), trie.hash());
+
+        // the collapsed branch must be merged with the resolved sibling leaf
+        let resolver = TestResolver(HashMap::from_iter([(sibling.hash(), *sibling.clone())]));
+        let mut resolved = sparse.clone();
+        assert!(resolved.delete_with_resolver(key.as_slice(), &resolver).unwrap());
+        assert_eq!(resolved.hash(), expected.hash());
+
+        // an orphan that cannot be resolved must be reported instead of assumed to be a branch
+        let mut unresolved = sparse.clone();
+        assert!(matches!(
+            unresolved.delete(key.as_slice()),
+            Err(Error::NodeNotResolved(digest)) if digest == sibling.hash()
+        ));
+
+        // a resolved node must match the requested digest
+        let resolver = TestResolver(HashMap::from_iter([(sibling.hash(), MptNode::default())]));
+        let mut resolved = sparse;
+        assert!(matches!(
+            resolved.delete_with_resolver(key.as_slice(), &resolver),
+            Err(Error::InvalidResolvedNode(digest)) if digest == sibling.hash()
+        ));
+    }
+
+    #[test]
+    pub fn test_prove() {
+        let mut trie = MptNode::default();
+        for i in 0..256u32 {
+            let key = keccak(i.to_be_bytes());
+            trie.insert_rlp(&key, i).unwrap();
+        }
+        let root = trie.hash();
+
+        for i in 0..512u32 {
+            let key = keccak(i.to_be_bytes());
+            let proof = trie.prove(&key).unwrap();
+            let expected = (i < 256).then(|| alloy_rlp::encode(i));
+
+            assert_eq!(verify_proof(root, &key, &proof).unwrap(), expected);
+            alloy_trie::proof::verify_proof(
+                root,
+                alloy_trie::Nibbles::unpack(key),
+                expected,
+                &proof,
+            )
+            .unwrap();
+        }
+
+        // a proof must not verify against a different root
+        let key = keccak(0u32.to_be_bytes());
+        let proof = trie.prove(&key).unwrap();
+        assert!(matches!(
+            verify_proof(B256::repeat_byte(0x11), &key, &proof),
+            Err(FromProofError::MismatchedProofRoot(found, _)) if found == root
+        ));
+
+        // the empty trie has an empty proof
+        let proof = MptNode::default().prove(&key).unwrap();
+        assert!(proof.is_empty());
+        assert_eq!(verify_proof(EMPTY_ROOT, &key, &proof).unwrap(), None);
+    }
+
+    #[test]
+    pub fn test_account_proof() {
+        use alloy_primitives::U256;
+        use reth_trie::TrieAccount;
+
+        let address = Address::repeat_byte(0x11);
+        let hashed_address = keccak(address);
+        let slot = B256::repeat_byte(0x22);
+
+        let mut storage_trie = MptNode::default();
+        storage_trie.insert_rlp(&keccak(slot), U256::from(42)).unwrap();
+        let account = TrieAccount {
+            nonce: 1,
+            balance: U256::from(100),
+            storage_root: storage_trie.hash(),
+            code_hash: KECCAK_EMPTY,
+        };
+        let mut state_trie = MptNode::default();
+        state_trie.insert_rlp(&hashed_address, &account).unwrap();
+        state_trie.insert_rlp(&keccak(Address::repeat_byte(0x33)), &account).unwrap();
+
+        let state = EthereumState {
+            state_trie,
+            storage_tries: HashMap::from_iter([(hashed_address.into(), storage_trie)]),
+        };
+
+        let proof = state.account_proof(address, &[slot, B256::ZERO]).unwrap();
+        assert_eq!(proof.nonce, 1);
+        assert_eq!(proof.storage_hash, account.storage_root);
+        assert_eq!(
+            verify_proof(state.state_root(), &hashed_address, &proof.account_proof).unwrap(),
+            Some(alloy_rlp::encode(&account))
+        );
+        assert_eq!(proof.storage_proof[0].value, U256::from(42));
+        assert_eq!(proof.storage_proof[1].value, U256::ZERO);
+        for storage_proof in &proof.storage_proof {
+            let key = keccak(storage_proof.key.as_b256());
+            let value = verify_proof(proof.storage_hash, &key, &storage_proof.proof).unwrap();
+            assert_eq!(value, (!storage_proof.value.is_zero()).then(|| alloy_rlp::encode(42u64)));
+        }
+
+        // accounts that do not exist are all zero
+        let proof = state.account_proof(Address::repeat_byte(0x44), &[slot]).unwrap();
+        assert_eq!(proof.storage_hash, B256::ZERO);
+        assert_eq!(proof.storage_proof[0].value, U256::ZERO);
+        assert!(proof.storage_proof[0].proof.is_empty());
+    }
+
     #[test]
     pub fn test_branch_value() {
         let mut trie = MptNode::default();
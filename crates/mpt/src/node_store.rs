@@ -0,0 +1,228 @@
+use alloy_primitives::{
+    map::{HashMap, HashSet},
+    Bytes, B256,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    mpt::{keccak, node_from_digest, resolve_nodes, Error, MptNode, MptNodeData, MptNodeReference},
+    ArenaEthereumState, ArenaTrie, EthereumState,
+};
+
+/// [EthereumState] as a flat list of unique RLP-encoded nodes, which is how the host passes the
+/// witness to the client programs.
+///
+/// The nodes are keyed by their reference, i.e. the hash of their encoding, and every trie is
+/// given by the hash of its root. Subtrees that occur several times, e.g. the storage tries of
+/// contracts with the same storage, are only stored once, and nodes shorter than 32 bytes are
+/// inlined in the nodes referencing them. The tries are rebuilt once inside the client.
+///
+/// This is an alternative to the serialization of [EthereumState] itself, which remains the format
+/// of the cached client inputs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeStore {
+    /// The RLP encodings of the nodes referenced by their hash.
+    nodes: Vec<Bytes>,
+    /// The root hash of the state trie.
+    state_root: B256,
+    /// The root hashes of the storage tries, by hashed address.
+    storage_roots: Vec<(B256, B256)>,
+}
+
+/// Collects the unique nodes of several tries.
+#[derive(Debug, Default)]
+struct NodeStoreBuilder {
+    nodes: Vec<Bytes>,
+    digests: HashSet<B256>,
+}
+
+impl NodeStoreBuilder {
+    /// Adds the nodes of the trie, and returns its root hash.
+    fn push_root(&mut self, root: &MptNode) -> B256 {
+        let digest = root.hash();
+        if !matches!(root.as_data(), MptNodeData::Null | MptNodeData::Digest(_)) {
+            self.push(digest, root);
+        }
+        digest
+    }
+
+    /// Adds the node with the given hash, and the nodes it references by their hash.
+    fn push(&mut self, digest: B256, node: &MptNode) {
+        // the subtree below a known hash has been added already
+        if !self.digests.insert(digest) {
+            return;
+        }
+        self.nodes.push(alloy_rlp::encode(node).into());
+
+        match node.as_data() {
+            MptNodeData::Branch(children) => {
+                children.iter().flatten().for_each(|child| self.push_child(child))
+            }
+            MptNodeData::Extension(_, child) => self.push_child(child),
+            MptNodeData::Null | MptNodeData::Leaf(_, _) | MptNodeData::Digest(_) => {}
+        }
+    }
+
+    fn push_child(&mut self, child: &MptNode) {
+        // nodes shorter than 32 bytes are part of their parent's encoding, and are too short to
+        // reference another node by its hash
+        if let MptNodeReference::Digest(digest) = child.reference() {
+            if !matches!(child.as_data(), MptNodeData::Digest(_)) {
+                self.push(digest, child);
+            }
+        }
+    }
+}
+
+impl From<&EthereumState> for NodeStore {
+    fn from(state: &EthereumState) -> Self {
+        let mut builder = NodeStoreBuilder::default();
+        let state_root = builder.push_root(&state.state_trie);
+        let storage_roots = state
+            .storage_tries
+            .iter()
+            .map(|(hashed_address, storage_trie)| {
+                (*hashed_address, builder.push_root(storage_trie))
+            })
+            .collect();
+
+        Self { nodes: builder.nodes, state_root, storage_roots }
+    }
+}
+
+impl TryFrom<NodeStore> for EthereumState {
+    type Error = Error;
+
+    /// Decodes every node once, and resolves the root hashes with them. Hashes that are not in
+    /// the store remain digests.
+    fn try_from(store: NodeStore) -> Result<Self, Self::Error> {
+        let nodes = store
+            .nodes
+            .iter()
+            .map(|rlp| Ok((MptNodeReference::Digest(keccak(rlp).into()), MptNode::decode(rlp)?)))
+            .collect::<Result<HashMap<_, _>, Error>>()?;
+
+        let resolve = |root| resolve_nodes(&node_from_digest(root), &nodes);
+        let state_trie = resolve(store.state_root);
+        let storage_tries = store
+            .storage_roots
+            .into_iter()
+            .map(|(hashed_address, root)| (hashed_address, resolve(root)))
+            .collect();
+
+        Ok(Self { state_trie, storage_tries })
+    }
+}
+
+impl TryFrom<NodeStore> for ArenaEthereumState {
+    type Error = Error;
+
+    /// Builds the arena of every trie directly from the encoded nodes, without going through
+    /// [MptNode]s. Subtrees shared by several tries are copied into each of them.
+    fn try_from(store: NodeStore) -> Result<Self, Self::Error> {
+        let nodes: HashMap<B256, &[u8]> =
+            store.nodes.iter().map(|rlp| (keccak(rlp).into(), rlp.as_ref())).collect();
+
+        let state_trie = ArenaTrie::from_rlp_nodes(&nodes, store.state_root)?;
+        let storage_tries = store
+            .storage_roots
+            .iter()
+            .map(|(hashed_address, root)| {
+                Ok((*hashed_address, ArenaTrie::from_rlp_nodes(&nodes, *root)?))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Self { state_trie, storage_tries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trie(entries: impl IntoIterator<Item = (u32, u32)>) -> MptNode {
+        let mut trie = MptNode::default();
+        for (key, value) in entries {
+            trie.insert_rlp(&keccak(key.to_be_bytes()), value).unwrap();
+        }
+        trie
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let storage_trie = trie((0..50).map(|i| (i, i)));
+        let sparse_trie: MptNode = MptNodeData::Digest(trie((0..10).map(|i| (i, i))).hash()).into();
+
+        let state_trie = trie((0..100).map(|i| (i, i + 1)));
+        let state = EthereumState {
+            state_trie: state_trie.clone(),
+            storage_tries: HashMap::from_iter([
+                (B256::repeat_byte(0x11), storage_trie.clone()),
+                (B256::repeat_byte(0x22), storage_trie.clone()),
+                (B256::repeat_byte(0x33), sparse_trie),
+                (B256::repeat_byte(0x44), MptNode::default()),
+                (B256::repeat_byte(0x55), trie([(1, 1)])),
+            ]),
+        };
+
+        let store = NodeStore::from(&state);
+        // the identical storage tries are only stored once, and the digest and the empty trie
+        // are only referenced by their root hash
+        let node_count = |trie: MptNode| {
+            NodeStore::from(&EthereumState { state_trie: trie, storage_tries: HashMap::default() })
+                .nodes
+                .len()
+        };
+        assert_eq!(
+            store.nodes.len(),
+            node_count(state_trie) + node_count(storage_trie) + node_count(trie([(1, 1)]))
+        );
+
+        let rebuilt = EthereumState::try_from(store).unwrap();
+        assert_eq!(rebuilt, state);
+    }
+
+    #[test]
+    fn test_into_arena() {
+        let storage_trie = trie((0..50).map(|i| (i, i)));
+        let state = EthereumState {
+            state_trie: trie((0..100).map(|i| (i, i + 1))),
+            storage_tries: HashMap::from_iter([
+                (B256::repeat_byte(0x11), storage_trie.clone()),
+                (B256::repeat_byte(0x22), storage_trie),
+                (B256::repeat_byte(0x33), MptNodeData::Digest(B256::repeat_byte(0xaa)).into()),
+                (B256::repeat_byte(0x44), MptNode::default()),
+            ]),
+        };
+
+        let mut arena = ArenaEthereumState::try_from(NodeStore::from(&state)).unwrap();
+        assert_eq!(arena.state_root(), state.state_root());
+        for (hashed_address, storage_trie) in &state.storage_tries {
+            assert_eq!(arena.storage_tries[hashed_address].hash(), storage_trie.hash());
+        }
+        let key = keccak(7u32.to_be_bytes());
+        assert_eq!(arena.state_trie.get(&key).unwrap(), state.state_trie.get(&key).unwrap());
+
+        // the storage tries sharing their nodes in the store are updated independently
+        arena.storage_tries.get_mut(&B256::repeat_byte(0x11)).unwrap().delete(&key).unwrap();
+        assert_ne!(
+            arena.storage_tries[&B256::repeat_byte(0x11)].hash(),
+            arena.storage_tries[&B256::repeat_byte(0x22)].hash()
+        );
+        assert_eq!(
+            arena.storage_tries[&B256::repeat_byte(0x22)].hash(),
+            state.storage_tries[&B256::repeat_byte(0x22)].hash()
+        );
+    }
+
+    #[test]
+    fn test_invalid_nodes() {
+        let store = NodeStore {
+            nodes: vec![Bytes::from_static(&[0xc3, 0x01, 0x02, 0x03])],
+            state_root: keccak([0xc3, 0x01, 0x02, 0x03]).into(),
+            storage_roots: vec![],
+        };
+        assert!(EthereumState::try_from(store.clone()).is_err());
+        assert!(ArenaEthereumState::try_from(store).is_err());
+    }
+}
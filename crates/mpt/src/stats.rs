@@ -0,0 +1,219 @@
+use core::fmt;
+
+use alloy_primitives::B256;
+use alloy_rlp::Encodable;
+
+use crate::{
+    mpt::{MptNode, MptNodeData},
+    EthereumState,
+};
+
+/// The number of storage tries listed individually in the [StateStats] summary.
+const LARGEST_STORAGE_TRIES: usize = 10;
+
+/// Statistics about the nodes of a single trie.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrieStats {
+    /// The number of branch nodes.
+    pub branches: usize,
+    /// The number of extension nodes.
+    pub extensions: usize,
+    /// The number of leaf nodes.
+    pub leaves: usize,
+    /// The number of unresolved nodes, only known by their digest.
+    pub digests: usize,
+    /// The number of nodes on the longest path from the root, including the root.
+    pub max_depth: usize,
+    /// The size of the RLP encodings of the root and of the nodes referenced by their digest.
+    ///
+    /// Nodes shorter than 32 bytes are embedded in their parent and only counted there.
+    pub encoded_size: usize,
+}
+
+impl TrieStats {
+    /// Collects the statistics of the trie rooted at `root`.
+    pub fn new(root: &MptNode) -> Self {
+        let mut stats = Self::default();
+        if !root.is_empty() {
+            stats.visit(root, 1);
+            stats.encoded_size += if root.is_digest() { 0 } else { root.length() };
+        }
+        stats
+    }
+
+    /// Returns the number of branch, extension, leaf and digest nodes.
+    pub fn nodes(&self) -> usize {
+        self.branches + self.extensions + self.leaves + self.digests
+    }
+
+    fn visit(&mut self, node: &MptNode, depth: usize) {
+        self.max_depth = self.max_depth.max(depth);
+
+        let children: &[_] = match node.as_data() {
+            MptNodeData::Null => &[],
+            MptNodeData::Branch(children) => {
+                self.branches += 1;
+                children
+            }
+            MptNodeData::Leaf(_, _) => {
+                self.leaves += 1;
+                &[]
+            }
+            MptNodeData::Extension(_, child) => {
+                self.extensions += 1;
+                self.visit_child(child, depth);
+                &[]
+            }
+            MptNodeData::Digest(_) => {
+                self.digests += 1;
+                &[]
+            }
+        };
+
+        for child in children.iter().flatten() {
+            self.visit_child(child, depth);
+        }
+    }
+
+    fn visit_child(&mut self, child: &MptNode, depth: usize) {
+        if !child.is_digest() {
+            let length = child.length();
+            if length >= 32 {
+                self.encoded_size += length;
+            }
+        }
+        self.visit(child, depth + 1);
+    }
+}
+
+impl core::ops::AddAssign for TrieStats {
+    /// Sums the node counts and sizes, and keeps the larger depth.
+    fn add_assign(&mut self, other: Self) {
+        self.branches += other.branches;
+        self.extensions += other.extensions;
+        self.leaves += other.leaves;
+        self.digests += other.digests;
+        self.max_depth = self.max_depth.max(other.max_depth);
+        self.encoded_size += other.encoded_size;
+    }
+}
+
+impl fmt::Display for TrieStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} nodes ({} branches, {} extensions, {} leaves, {} digests), max depth {}, {} bytes",
+            self.nodes(),
+            self.branches,
+            self.extensions,
+            self.leaves,
+            self.digests,
+            self.max_depth,
+            self.encoded_size
+        )
+    }
+}
+
+/// Statistics about the tries of an [EthereumState], see [EthereumState::stats].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateStats {
+    /// The statistics of the state trie.
+    pub state_trie: TrieStats,
+    /// The statistics of every storage trie by hashed address, from the largest to the
+    /// smallest number of nodes.
+    pub storage_tries: Vec<(B256, TrieStats)>,
+}
+
+impl StateStats {
+    /// Returns the combined statistics of all storage tries.
+    pub fn storage_total(&self) -> TrieStats {
+        let mut total = TrieStats::default();
+        for (_, stats) in &self.storage_tries {
+            total += *stats;
+        }
+        total
+    }
+}
+
+impl fmt::Display for StateStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "state trie: {}", self.state_trie)?;
+        write!(f, "{} storage tries: {}", self.storage_tries.len(), self.storage_total())?;
+        for (hashed_address, stats) in self.storage_tries.iter().take(LARGEST_STORAGE_TRIES) {
+            write!(f, "\n  {}: {}", hashed_address, stats)?;
+        }
+        Ok(())
+    }
+}
+
+impl EthereumState {
+    /// Returns statistics about the nodes of the state trie and of each storage trie, which
+    /// describe what makes up the size of the witness.
+    pub fn stats(&self) -> StateStats {
+        let mut storage_tries: Vec<_> = self
+            .storage_tries
+            .iter()
+            .map(|(hashed_address, storage_trie)| (*hashed_address, TrieStats::new(storage_trie)))
+            .collect();
+        storage_tries.sort_by(|(a, a_stats), (b, b_stats)| {
+            b_stats.nodes().cmp(&a_stats.nodes()).then_with(|| a.cmp(b))
+        });
+
+        StateStats { state_trie: TrieStats::new(&self.state_trie), storage_tries }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::map::HashMap;
+
+    use super::*;
+    use crate::mpt::keccak;
+
+    #[test]
+    fn test_trie_stats() {
+        assert_eq!(TrieStats::new(&MptNode::default()), TrieStats::default());
+
+        let mut trie = MptNode::default();
+        for i in 0u32..100 {
+            trie.insert_rlp(&keccak(i.to_be_bytes()), i).unwrap();
+        }
+        let stats = TrieStats::new(&trie);
+        assert_eq!(stats.leaves, 100);
+        assert_eq!(stats.nodes(), trie.size());
+        assert_eq!(stats.digests, 0);
+        assert!(stats.max_depth >= 3);
+        assert!(stats.encoded_size >= trie.length());
+
+        // a sparse trie only reports the digest
+        let sparse: MptNode = MptNodeData::Digest(trie.hash()).into();
+        let stats = TrieStats::new(&sparse);
+        assert_eq!(
+            (stats.digests, stats.nodes(), stats.max_depth, stats.encoded_size),
+            (1, 1, 1, 0)
+        );
+    }
+
+    #[test]
+    fn test_state_stats() {
+        let mut small = MptNode::default();
+        small.insert_rlp(&keccak([0u8]), 1u32).unwrap();
+        let mut large = MptNode::default();
+        for i in 0u32..10 {
+            large.insert_rlp(&keccak(i.to_be_bytes()), i).unwrap();
+        }
+
+        let state = EthereumState {
+            state_trie: MptNode::default(),
+            storage_tries: HashMap::from_iter([
+                (B256::repeat_byte(0x11), small.clone()),
+                (B256::repeat_byte(0x22), large.clone()),
+            ]),
+        };
+        let stats = state.stats();
+        assert_eq!(stats.state_trie, TrieStats::default());
+        assert_eq!(stats.storage_tries[0], (B256::repeat_byte(0x22), TrieStats::new(&large)));
+        assert_eq!(stats.storage_tries[1], (B256::repeat_byte(0x11), TrieStats::new(&small)));
+        assert_eq!(stats.storage_total().leaves, 11);
+    }
+}
@@ -13,6 +13,26 @@ pub fn op_mainnet() -> eyre::Result<OpChainSpec> {
     (&Genesis::OpMainnet).try_into()
 }
 
+/// Returns the [OpChainSpec] for OP Sepolia.
+pub fn op_sepolia() -> eyre::Result<OpChainSpec> {
+    (&Genesis::OpSepolia).try_into()
+}
+
+/// Returns the [OpChainSpec] for Base.
+pub fn base_mainnet() -> eyre::Result<OpChainSpec> {
+    (&Genesis::Base).try_into()
+}
+
+/// Returns the [OpChainSpec] for Base Sepolia.
+pub fn base_sepolia() -> eyre::Result<OpChainSpec> {
+    (&Genesis::BaseSepolia).try_into()
+}
+
+/// Returns the [OpChainSpec] for Unichain.
+pub fn unichain_mainnet() -> eyre::Result<OpChainSpec> {
+    (&Genesis::Unichain).try_into()
+}
+
 /// Returns the [ChainSpec] for Linea Mainnet.
 pub fn linea_mainnet() -> eyre::Result<ChainSpec> {
     (&Genesis::Linea).try_into()
@@ -25,7 +45,15 @@ pub fn sepolia() -> eyre::Result<ChainSpec> {
 
 #[cfg(test)]
 mod tests {
-    use crate::chain_spec::{linea_mainnet, op_mainnet, sepolia};
+    use reth_optimism_forks::{OpHardfork, OpHardforks};
+
+    use crate::{
+        chain_spec::{
+            base_mainnet, base_sepolia, linea_mainnet, op_mainnet, op_sepolia, sepolia,
+            unichain_mainnet,
+        },
+        genesis::{Genesis, UNICHAIN_GENESIS_JSON},
+    };
 
     use super::mainnet;
 
@@ -43,6 +71,62 @@ mod tests {
         assert_eq!(10, chain_spec.chain.id(), "the chain id must be 10 for OP mainnet");
     }
 
+    #[test]
+    pub fn test_op_stack_chain_specs() {
+        assert_eq!(11155420, op_sepolia().unwrap().chain.id());
+        assert_eq!(8453, base_mainnet().unwrap().chain.id());
+        assert_eq!(84532, base_sepolia().unwrap().chain.id());
+
+        let chain_spec = unichain_mainnet().unwrap();
+        assert_eq!(130, chain_spec.chain.id(), "the chain id must be 130 for Unichain");
+        assert!(chain_spec.is_granite_active_at_timestamp(0));
+        assert!(!chain_spec.is_holocene_active_at_timestamp(1736445600));
+        assert!(chain_spec.is_holocene_active_at_timestamp(1736445601));
+        let isthmus = chain_spec.op_fork_activation(OpHardfork::Isthmus);
+        assert!(!isthmus.active_at_timestamp(1746806400));
+        assert!(isthmus.active_at_timestamp(1746806401));
+        let base_fee_params = chain_spec.base_fee_params_at_timestamp(0);
+        assert_eq!(base_fee_params.elasticity_multiplier, 2);
+        assert_eq!(base_fee_params.max_change_denominator, 250);
+    }
+
+    #[test]
+    pub fn test_custom_op_stack_chain_spec() {
+        let genesis = Genesis::Custom(UNICHAIN_GENESIS_JSON.to_string());
+        assert!(genesis.is_optimism());
+        assert!(!Genesis::Custom(crate::genesis::LINEA_GENESIS_JSON.to_string()).is_optimism());
+
+        let chain_spec: reth_optimism_chainspec::OpChainSpec = (&genesis).try_into().unwrap();
+        assert_eq!(chain_spec.chain.id(), unichain_mainnet().unwrap().chain.id());
+    }
+
+    #[test]
+    pub fn test_config_hash() {
+        let custom = |json: &str| Genesis::Custom(json.to_string()).config_hash().unwrap();
+
+        // The hash does not depend on the formatting of the genesis.
+        let hash = custom(r#"{"config":{"chainId":1337,"londonBlock":0,"shanghaiTime":1000}}"#);
+        assert_eq!(
+            hash,
+            custom(r#"{ "config": { "shanghaiTime": 1000, "londonBlock": 0, "chainId": 1337 } }"#)
+        );
+
+        // Changing a fork timestamp changes the hash.
+        assert_ne!(
+            hash,
+            custom(r#"{"config":{"chainId":1337,"londonBlock":0,"shanghaiTime":1001}}"#)
+        );
+
+        // A built-in genesis is hashed like the chain spec it resolves to.
+        let unichain = Genesis::Custom(UNICHAIN_GENESIS_JSON.to_string());
+        assert_eq!(unichain.config_hash().unwrap(), Genesis::Unichain.config_hash().unwrap());
+        assert_ne!(Genesis::OpMainnet.config_hash().unwrap(), Genesis::Base.config_hash().unwrap());
+        assert_ne!(
+            Genesis::Mainnet.config_hash().unwrap(),
+            Genesis::Sepolia.config_hash().unwrap()
+        );
+    }
+
     #[test]
     pub fn test_linea_mainnet_chain_spec() {
         let chain_spec = linea_mainnet().unwrap();
@@ -1,10 +1,28 @@
+use alloy_primitives::{address, b256, keccak256, B256};
 use eyre::eyre;
-use reth_chainspec::{BaseFeeParams, BaseFeeParamsKind, Chain, ChainSpec, EthereumHardfork};
+use reth_chainspec::{
+    BaseFeeParams, BaseFeeParamsKind, Chain, ChainHardforks, ChainSpec, DepositContract,
+    EthereumHardfork, ForkCondition, Hardfork,
+};
 use reth_optimism_chainspec::OpChainSpec;
 use reth_optimism_forks::OpHardfork;
 use serde::{Deserialize, Serialize};
 
 pub const LINEA_GENESIS_JSON: &str = include_str!("../../../bin/host/genesis/59144.json");
+pub const UNICHAIN_GENESIS_JSON: &str = include_str!("../../../bin/host/genesis/130.json");
+
+/// The timestamp of the Prague hardfork on mainnet.
+const MAINNET_PRAGUE_TIMESTAMP: u64 = 1746612311;
+
+/// The timestamp of the Isthmus hardfork on the OP Stack mainnets of the Superchain.
+const OP_MAINNET_ISTHMUS_TIMESTAMP: u64 = 1746806401;
+
+/// The timestamp of the Isthmus hardfork on the OP Stack testnets of the Superchain.
+const OP_SEPOLIA_ISTHMUS_TIMESTAMP: u64 = 1744905600;
+
+/// The topic of the `DepositEvent` log emitted by the beacon chain deposit contract.
+const DEPOSIT_EVENT_TOPIC: B256 =
+    b256!("649bbc62d0e31342afea4e5cd82d4049e7e1ee912fc0889aa790803be39038c5");
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Genesis {
@@ -12,6 +30,10 @@ pub enum Genesis {
     OpMainnet,
     Sepolia,
     Linea,
+    Base,
+    OpSepolia,
+    BaseSepolia,
+    Unichain,
     Custom(String),
 }
 
@@ -22,6 +44,40 @@ pub fn genesis_from_json(json: &str) -> eyre::Result<alloy_genesis::Genesis> {
     Ok(genesis)
 }
 
+impl Genesis {
+    /// Returns the hash of the chain config, which identifies the rules the blocks of the chain
+    /// are executed with, see [chain_config_hash].
+    pub fn config_hash(&self) -> eyre::Result<B256> {
+        if self.is_optimism() {
+            let chain_spec: OpChainSpec = self.try_into()?;
+            Ok(chain_config_hash(&chain_spec.inner))
+        } else {
+            let chain_spec: ChainSpec = self.try_into()?;
+            Ok(chain_config_hash(&chain_spec))
+        }
+    }
+
+    /// Returns whether the chain is an OP Stack chain.
+    ///
+    /// A custom genesis describes an OP Stack chain when it sets the Bedrock block or the
+    /// `optimism` EIP-1559 parameters.
+    pub fn is_optimism(&self) -> bool {
+        match self {
+            Genesis::OpMainnet |
+            Genesis::Base |
+            Genesis::OpSepolia |
+            Genesis::BaseSepolia |
+            Genesis::Unichain => true,
+            Genesis::Custom(json) => genesis_from_json(json).is_ok_and(|genesis| {
+                ["bedrockBlock", "optimism"]
+                    .iter()
+                    .any(|key| genesis.config.extra_fields.contains_key(*key))
+            }),
+            Genesis::Mainnet | Genesis::Sepolia | Genesis::Linea => false,
+        }
+    }
+}
+
 impl TryFrom<u64> for Genesis {
     type Error = eyre::Error;
 
@@ -29,8 +85,12 @@ impl TryFrom<u64> for Genesis {
         match value {
             1 => Ok(Genesis::Mainnet),
             10 => Ok(Genesis::OpMainnet),
+            130 => Ok(Genesis::Unichain),
+            8453 => Ok(Genesis::Base),
             59144 => Ok(Genesis::Linea),
+            84532 => Ok(Genesis::BaseSepolia),
             11155111 => Ok(Genesis::Sepolia),
+            11155420 => Ok(Genesis::OpSepolia),
             id => Err(eyre!("The chain {id} is not supported")),
         }
     }
@@ -42,14 +102,24 @@ impl TryFrom<&Genesis> for ChainSpec {
     fn try_from(value: &Genesis) -> Result<Self, Self::Error> {
         match value {
             Genesis::Mainnet => {
+                let mut hardforks: ChainHardforks = EthereumHardfork::mainnet().into();
+                hardforks.insert(
+                    EthereumHardfork::Prague,
+                    ForkCondition::Timestamp(MAINNET_PRAGUE_TIMESTAMP),
+                );
+
                 let mainnet = ChainSpec {
                     chain: Chain::mainnet(),
                     genesis_hash: Default::default(),
                     genesis: Default::default(),
                     genesis_header: Default::default(),
                     paris_block_and_final_difficulty: Default::default(),
-                    hardforks: EthereumHardfork::mainnet().into(),
-                    deposit_contract: Default::default(),
+                    hardforks,
+                    deposit_contract: Some(DepositContract::new(
+                        address!("00000000219ab540356cbb839cbe05303d7705fa"),
+                        11052984,
+                        DEPOSIT_EVENT_TOPIC,
+                    )),
                     base_fee_params: BaseFeeParamsKind::Constant(BaseFeeParams::ethereum()),
                     prune_delete_limit: 20000,
                     blob_params: Default::default(),
@@ -65,15 +135,23 @@ impl TryFrom<&Genesis> for ChainSpec {
                     genesis_header: Default::default(),
                     paris_block_and_final_difficulty: Default::default(),
                     hardforks: EthereumHardfork::sepolia().into(),
-                    deposit_contract: Default::default(),
+                    deposit_contract: Some(DepositContract::new(
+                        address!("7f02c3e3c98b133055b8b348b2ac625669ed295d"),
+                        1273020,
+                        DEPOSIT_EVENT_TOPIC,
+                    )),
                     base_fee_params: BaseFeeParamsKind::Constant(BaseFeeParams::ethereum()),
                     prune_delete_limit: 10000,
                     blob_params: Default::default(),
                 };
                 Ok(sepolia)
             }
-            Genesis::OpMainnet => {
-                Err(eyre!("Only converting Genesis::OpMainnet to OpChainSpec is supported"))
+            Genesis::OpMainnet |
+            Genesis::Base |
+            Genesis::OpSepolia |
+            Genesis::BaseSepolia |
+            Genesis::Unichain => {
+                Err(eyre!("OP Stack chains can only be converted to an OpChainSpec"))
             }
             Genesis::Linea => Ok(ChainSpec::from_genesis(genesis_from_json(LINEA_GENESIS_JSON)?)),
             Genesis::Custom(json) => Ok(ChainSpec::from_genesis(genesis_from_json(json)?)),
@@ -86,25 +164,150 @@ impl TryFrom<&Genesis> for OpChainSpec {
 
     fn try_from(value: &Genesis) -> Result<Self, Self::Error> {
         match value {
-            Genesis::OpMainnet => {
-                let op_mainnet = OpChainSpec {
-                    inner: ChainSpec {
-                        chain: Chain::optimism_mainnet(),
-                        genesis_hash: Default::default(),
-                        genesis: Default::default(),
-                        genesis_header: Default::default(),
-                        paris_block_and_final_difficulty: Default::default(),
-                        hardforks: OpHardfork::op_mainnet(),
-                        deposit_contract: Default::default(),
-                        base_fee_params: BaseFeeParamsKind::Constant(BaseFeeParams::ethereum()),
-                        prune_delete_limit: 10000,
-                        blob_params: Default::default(),
-                    },
-                };
+            Genesis::OpMainnet => Ok(op_chain_spec(
+                Chain::optimism_mainnet(),
+                with_isthmus(OpHardfork::op_mainnet(), OP_MAINNET_ISTHMUS_TIMESTAMP),
+                BaseFeeParams::optimism(),
+                BaseFeeParams::optimism_canyon(),
+            )),
+            Genesis::OpSepolia => Ok(op_chain_spec(
+                Chain::optimism_sepolia(),
+                with_isthmus(OpHardfork::op_sepolia(), OP_SEPOLIA_ISTHMUS_TIMESTAMP),
+                BaseFeeParams::optimism_sepolia(),
+                BaseFeeParams::optimism_sepolia_canyon(),
+            )),
+            Genesis::Base => Ok(op_chain_spec(
+                Chain::base_mainnet(),
+                with_isthmus(OpHardfork::base_mainnet(), OP_MAINNET_ISTHMUS_TIMESTAMP),
+                BaseFeeParams::optimism(),
+                BaseFeeParams::optimism_canyon(),
+            )),
+            Genesis::BaseSepolia => Ok(op_chain_spec(
+                Chain::base_sepolia(),
+                with_isthmus(OpHardfork::base_sepolia(), OP_SEPOLIA_ISTHMUS_TIMESTAMP),
+                BaseFeeParams::base_sepolia(),
+                BaseFeeParams::base_sepolia_canyon(),
+            )),
+            Genesis::Unichain => Ok(OpChainSpec::from(genesis_from_json(UNICHAIN_GENESIS_JSON)?)),
+            Genesis::Custom(json) if value.is_optimism() => {
+                Ok(OpChainSpec::from(genesis_from_json(json)?))
+            }
+            _ => Err(eyre!("The genesis is not the one of an OP Stack chain")),
+        }
+    }
+}
 
-                Ok(op_mainnet)
+/// Returns the hash of the rules a chain spec executes blocks with.
+///
+/// The hash is over a canonical encoding of the resolved chain spec, so that it does not depend
+/// on how the chain was specified: the chain id, the hardforks sorted by name with their
+/// activation conditions, the EIP-1559 parameters from genesis and from each activation, the
+/// deposit contract and whether the chain uses Clique.
+pub fn chain_config_hash(chain_spec: &ChainSpec) -> B256 {
+    let mut encoded = Vec::new();
+    encoded.extend_from_slice(&chain_spec.chain.id().to_be_bytes());
+
+    let mut hardforks: Vec<_> = chain_spec
+        .hardforks
+        .forks_iter()
+        .map(|(fork, condition)| (fork.name(), condition))
+        .collect();
+    hardforks.sort_by_key(|(name, _)| *name);
+
+    encode_base_fee_params(&mut encoded, chain_spec.base_fee_params_at_block(0));
+    encoded.extend_from_slice(&(hardforks.len() as u64).to_be_bytes());
+    for (name, condition) in hardforks {
+        encoded.extend_from_slice(&(name.len() as u64).to_be_bytes());
+        encoded.extend_from_slice(name.as_bytes());
+        match condition {
+            ForkCondition::Block(block) => {
+                encoded.push(0);
+                encoded.extend_from_slice(&block.to_be_bytes());
+                encode_base_fee_params(&mut encoded, chain_spec.base_fee_params_at_block(block));
+            }
+            ForkCondition::Timestamp(timestamp) => {
+                encoded.push(1);
+                encoded.extend_from_slice(&timestamp.to_be_bytes());
+                encode_base_fee_params(
+                    &mut encoded,
+                    chain_spec.base_fee_params_at_timestamp(timestamp),
+                );
             }
-            _ => Err(eyre!("Only converting Genesis::OpMainnet to OpChainSpec is supported")),
+            ForkCondition::TTD { fork_block, total_difficulty, .. } => {
+                encoded.push(2);
+                encoded.extend_from_slice(&total_difficulty.to_be_bytes::<32>());
+                encode_option(&mut encoded, fork_block.map(u64::to_be_bytes));
+            }
+            ForkCondition::Never => encoded.push(3),
         }
     }
+
+    encode_option(
+        &mut encoded,
+        chain_spec.deposit_contract.as_ref().map(|deposit_contract| {
+            [
+                deposit_contract.address.as_slice(),
+                &deposit_contract.block.to_be_bytes(),
+                deposit_contract.topic.as_slice(),
+            ]
+            .concat()
+        }),
+    );
+    encoded.push(chain_spec.genesis.config.clique.is_some() as u8);
+
+    keccak256(encoded)
+}
+
+/// Appends the EIP-1559 parameters to `encoded`.
+fn encode_base_fee_params(encoded: &mut Vec<u8>, base_fee_params: BaseFeeParams) {
+    encoded.extend_from_slice(&base_fee_params.max_change_denominator.to_be_bytes());
+    encoded.extend_from_slice(&base_fee_params.elasticity_multiplier.to_be_bytes());
+}
+
+/// Appends an optional value to `encoded`, prefixed with whether it is present.
+fn encode_option(encoded: &mut Vec<u8>, value: Option<impl AsRef<[u8]>>) {
+    match value {
+        Some(value) => {
+            encoded.push(1);
+            encoded.extend_from_slice(value.as_ref());
+        }
+        None => encoded.push(0),
+    }
+}
+
+/// Schedules Isthmus, and the Prague features it includes, at `timestamp`.
+fn with_isthmus(mut hardforks: ChainHardforks, timestamp: u64) -> ChainHardforks {
+    hardforks.insert(EthereumHardfork::Prague, ForkCondition::Timestamp(timestamp));
+    hardforks.insert(OpHardfork::Isthmus, ForkCondition::Timestamp(timestamp));
+    hardforks
+}
+
+/// Returns the [OpChainSpec] of an OP Stack chain with the EIP-1559 parameters changing at
+/// Canyon.
+fn op_chain_spec(
+    chain: Chain,
+    hardforks: ChainHardforks,
+    base_fee_params: BaseFeeParams,
+    canyon_base_fee_params: BaseFeeParams,
+) -> OpChainSpec {
+    OpChainSpec {
+        inner: ChainSpec {
+            chain,
+            genesis_hash: Default::default(),
+            genesis: Default::default(),
+            genesis_header: Default::default(),
+            paris_block_and_final_difficulty: Default::default(),
+            hardforks,
+            deposit_contract: Default::default(),
+            base_fee_params: BaseFeeParamsKind::Variable(
+                vec![
+                    (EthereumHardfork::London.boxed(), base_fee_params),
+                    (OpHardfork::Canyon.boxed(), canyon_base_fee_params),
+                ]
+                .into(),
+            ),
+            prune_delete_limit: 10000,
+            blob_params: Default::default(),
+        },
+    }
 }